@@ -0,0 +1,39 @@
+use core::alloc::{AllocError, Allocator, Layout};
+use core::cell::RefCell;
+use core::ptr::NonNull;
+
+use crate::heap::Heap;
+
+/// A handle that lets standard collections like `Vec` and `Box` allocate
+/// from a [`Heap`](struct.Heap.html), through the nightly `Allocator` trait
+/// (enable the `allocator_api` feature).
+///
+/// The heap is shared through a `RefCell`, so like the heap itself, this
+/// is for single-threaded use only.
+///
+/// **Important**: The garbage collector doesn't know about these
+/// allocations. If you run a GC while a collection is alive, its memory
+/// must be reachable from your roots, or it will be freed out from under
+/// the collection.
+#[derive(Clone, Copy)]
+pub struct HeapAllocator<'a, 'heap> {
+    heap: &'a RefCell<Heap<'heap>>,
+}
+
+impl<'a, 'heap> HeapAllocator<'a, 'heap> {
+    /// Allocate out of a shared heap.
+    pub fn new(heap: &'a RefCell<Heap<'heap>>) -> HeapAllocator<'a, 'heap> {
+        HeapAllocator { heap }
+    }
+}
+
+unsafe impl<'a, 'heap> Allocator for HeapAllocator<'a, 'heap> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let m = self.heap.borrow_mut().allocate_aligned(layout.size(), layout.align()).ok_or(AllocError)?;
+        Ok(NonNull::from(m.inner()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        self.heap.borrow_mut().retire_span(ptr.as_ptr());
+    }
+}
@@ -1,10 +1,13 @@
 use core::fmt;
-use core::mem;
+use core::{mem, ptr};
 use crate::memory::Memory;
 
 // we need to reserve 2 bits per block for tracking.
 pub const BLOCKS_PER_COLORMAP_BYTE: usize = 8 / 2;
 
+/// The 2-bit color of each block in the color map. Blue and green alternate
+/// as the "live" color on each GC cycle, check marks a span that still needs
+/// to be scanned (or free memory), and continue marks the rest of a span.
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Color {
@@ -16,10 +19,27 @@ pub enum Color {
 
 impl Color {
     // why isn't this automatic or derivable?
-    pub fn from_bits(n: u8) -> Color {
+    // `n` must already be masked to 2 bits: only use this on the hot path
+    // where we read from our own bitmap.
+    pub(crate) fn from_bits(n: u8) -> Color {
+        debug_assert!(n <= 0b11);
         unsafe { mem::transmute(n) }
     }
 
+    /// Convert a 2-bit value (as found in
+    /// [`Heap::colormap_bytes`](struct.Heap.html#method.colormap_bytes))
+    /// into a color, or `None` if it's out of range.
+    pub fn try_from_bits(n: u8) -> Option<Color> {
+        match n {
+            0b00 => Some(Color::Continue),
+            0b01 => Some(Color::Blue),
+            0b10 => Some(Color::Green),
+            0b11 => Some(Color::Check),
+            _ => None,
+        }
+    }
+
+    /// The other live color: blue for green, and green for blue.
     pub fn opposite(&self) -> Color {
         match *self {
             Color::Blue => Color::Green,
@@ -46,14 +66,52 @@ pub struct BlockRange {
 // (free memory is tracked separately on a sorted FreeList.)
 pub struct ColorMap<'heap> {
     bits: &'heap mut [u8],
+    // for profiling: how many times a byte of the map has been written.
+    #[cfg(feature = "profiling")]
+    pub writes: usize,
 }
 
 impl<'heap> ColorMap<'heap> {
     pub fn new(m: Memory<'heap>) -> ColorMap<'heap> {
-        let bits = m.inner();
-        // mark whole area as "free" (check)
-        for i in 0..bits.len() { bits[i] = 0xff }
-        ColorMap { bits }
+        let mut map = ColorMap {
+            bits: m.inner(),
+            #[cfg(feature = "profiling")]
+            writes: 0,
+        };
+        map.clear();
+        map
+    }
+
+    // mark whole area as "free" (check)
+    pub fn clear(&mut self) {
+        for i in 0..self.bits.len() { self.bits[i] = 0xff }
+    }
+
+    // move the color map into a larger chunk of memory, which may overlap
+    // the current one. the new blocks at the end are marked "free" (check).
+    pub fn relocate(&mut self, m: Memory<'heap>) {
+        let old_len = self.bits.len();
+        assert!(m.len() >= old_len);
+        unsafe { ptr::copy(self.bits.as_ptr(), m.start(), old_len) };
+        self.bits = m.inner();
+        for i in old_len..self.bits.len() { self.bits[i] = 0xff }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bits
+    }
+
+    // overwrite the whole map from a saved copy of `as_bytes`.
+    pub fn copy_from(&mut self, bytes: &[u8]) {
+        self.bits.copy_from_slice(bytes);
+        #[cfg(feature = "profiling")]
+        { self.writes += bytes.len(); }
+    }
+
+    // address just past the end of the color map's memory
+    #[inline]
+    pub fn end(&self) -> *mut u8 {
+        ((self.bits.as_ptr() as usize) + self.bits.len()) as *mut u8
     }
 
     #[inline]
@@ -72,23 +130,50 @@ impl<'heap> ColorMap<'heap> {
         let mask = !(3 << shift);
         let replace = (color as u8) << shift;
         self.bits[n / 4] = (self.bits[n / 4] & mask) | replace;
+        #[cfg(feature = "profiling")]
+        { self.writes += 1; }
     }
 
-    pub fn get_range(&self, n: usize) -> BlockRange {
+    // the color map may have room for more blocks than the heap really
+    // has, so the scan stops at `max`.
+    pub fn get_range(&self, n: usize, max: usize) -> BlockRange {
+        debug_assert!(max <= self.len());
         let color = self.get(n);
         let mut end = n + 1;
-        while end < self.len() && self.get(end) == Color::Continue { end += 1 }
+        while end < max && self.get(end) == Color::Continue { end += 1 }
         BlockRange { start: n, end, color }
     }
 
     pub fn set_range(&mut self, range: BlockRange) {
         self.set(range.start, range.color);
-        for i in (range.start + 1)..(range.end) { self.set(i, Color::Continue) }
+        self.fill(range.start + 1, range.end, Color::Continue);
     }
 
     // free ranges must be marked with a run of "check" so they can terminate a previous range
     pub fn free_range(&mut self, range: BlockRange) {
-        for i in (range.start)..(range.end) { self.set(i, Color::Check) }
+        self.fill(range.start, range.end, Color::Check);
+    }
+
+    // set every block from `start` to `end` to the same color. whole bytes
+    // in the middle are written at once, and only the partial bytes at each
+    // end need a read-modify-write.
+    #[allow(clippy::manual_is_multiple_of)]
+    fn fill(&mut self, start: usize, end: usize, color: Color) {
+        let mut i = start;
+        while i < end && i % BLOCKS_PER_COLORMAP_BYTE != 0 {
+            self.set(i, color);
+            i += 1;
+        }
+        let whole = (end - i) / BLOCKS_PER_COLORMAP_BYTE;
+        let byte = (color as u8) * 0b01010101;
+        for b in &mut self.bits[i / BLOCKS_PER_COLORMAP_BYTE .. i / BLOCKS_PER_COLORMAP_BYTE + whole] { *b = byte }
+        #[cfg(feature = "profiling")]
+        { self.writes += whole; }
+        i += whole * BLOCKS_PER_COLORMAP_BYTE;
+        while i < end {
+            self.set(i, color);
+            i += 1;
+        }
     }
 
     fn dump<W: fmt::Write>(&self, buffer: &mut W) -> fmt::Result {
@@ -141,11 +226,65 @@ mod tests {
         let mut map = ColorMap::new(Memory::new(&mut data));
         map.set_range(BlockRange { start: 0, end: 2, color: Color::Green });
         assert_eq!(debug(&map, &mut buffer), "ColorMap(G.CCCCCCCCCCCCCC)");
-        assert_eq!(map.get_range(0), BlockRange { start: 0, end: 2, color: Color::Green });
+        assert_eq!(map.get_range(0, map.len()), BlockRange { start: 0, end: 2, color: Color::Green });
 
         map.set_range(BlockRange { start: 2, end: 3, color: Color::Blue });
-        assert_eq!(map.get_range(2), BlockRange { start: 2, end: 3, color: Color::Blue });
-        assert_eq!(map.get_range(0), BlockRange { start: 0, end: 2, color: Color::Green });
+        assert_eq!(map.get_range(2, map.len()), BlockRange { start: 2, end: 3, color: Color::Blue });
+        assert_eq!(map.get_range(0, map.len()), BlockRange { start: 0, end: 2, color: Color::Green });
         assert_eq!(debug(&map, &mut buffer), "ColorMap(G.BCCCCCCCCCCCCC)");
     }
+
+    #[test]
+    fn set_long_range() {
+        let mut data: [u8; 128] = [0; 128];
+        let mut map = ColorMap::new(Memory::new(&mut data));
+        let mut expected: [u8; 128] = [0; 128];
+        let mut slow = ColorMap::new(Memory::new(&mut expected));
+
+        // unaligned at both ends, with neighbors on each side.
+        for &(start, end, color) in [ (0, 3, Color::Blue), (3, 403, Color::Green), (403, 405, Color::Blue) ].iter() {
+            map.set_range(BlockRange { start, end, color });
+            slow.set(start, color);
+            for i in (start + 1)..end { slow.set(i, Color::Continue) }
+        }
+        assert_eq!(map.as_bytes(), slow.as_bytes());
+        assert_eq!(map.get_range(3, map.len()), BlockRange { start: 3, end: 403, color: Color::Green });
+        assert_eq!(map.get_range(403, map.len()), BlockRange { start: 403, end: 405, color: Color::Blue });
+        assert_eq!(map.get(405), Color::Check);
+    }
+
+    #[test]
+    fn free_long_range() {
+        let mut data: [u8; 128] = [0; 128];
+        let mut map = ColorMap::new(Memory::new(&mut data));
+        map.set_range(BlockRange { start: 0, end: 2, color: Color::Blue });
+        map.set_range(BlockRange { start: 2, end: 402, color: Color::Green });
+        map.set_range(BlockRange { start: 402, end: 403, color: Color::Blue });
+        map.set_range(BlockRange { start: 403, end: 405, color: Color::Green });
+
+        map.free_range(BlockRange { start: 2, end: 402, color: Color::Check });
+        assert!((2..402).all(|i| map.get(i) == Color::Check));
+        assert_eq!(map.get_range(0, map.len()), BlockRange { start: 0, end: 2, color: Color::Blue });
+        assert_eq!(map.get_range(402, map.len()), BlockRange { start: 402, end: 403, color: Color::Blue });
+        assert_eq!(map.get_range(403, map.len()), BlockRange { start: 403, end: 405, color: Color::Green });
+    }
+
+    #[test]
+    fn get_range_max() {
+        let mut data: [u8; 4] = [0; 4];
+        let mut map = ColorMap::new(Memory::new(&mut data));
+        map.set_range(BlockRange { start: 10, end: 16, color: Color::Blue });
+        assert_eq!(map.get_range(10, 16), BlockRange { start: 10, end: 16, color: Color::Blue });
+        assert_eq!(map.get_range(10, 13), BlockRange { start: 10, end: 13, color: Color::Blue });
+    }
+
+    #[test]
+    fn try_from_bits() {
+        assert_eq!(Color::try_from_bits(0), Some(Color::Continue));
+        assert_eq!(Color::try_from_bits(1), Some(Color::Blue));
+        assert_eq!(Color::try_from_bits(2), Some(Color::Green));
+        assert_eq!(Color::try_from_bits(3), Some(Color::Check));
+        assert_eq!(Color::try_from_bits(4), None);
+        assert_eq!(Color::try_from_bits(0xff), None);
+    }
 }
@@ -1,4 +1,5 @@
 use core::{fmt, mem, slice};
+use crate::floor_to;
 use crate::memory::Memory;
 
 // each free block is part of a linked list.
@@ -21,6 +22,7 @@ impl<'heap> FreeBlockPtr<'heap> {
     pub fn allocate(&self, amount: usize) -> Option<Memory<'heap>> {
         let s = self.as_mut();
         s.ptr.and_then(|block| {
+            block.check_canary();
             if amount > block.size {
                 None
             } else if block.size - amount < FREE_BLOCK_SIZE {
@@ -29,9 +31,26 @@ impl<'heap> FreeBlockPtr<'heap> {
                 Some(block.as_memory())
             } else {
                 // split off a new alloc
-                let (a1, a2) = block.as_memory().split_at(amount);
-                s.ptr = Some(FreeBlock::from_memory(a2, block.next));
-                Some(a1)
+                s.ptr = Some(block.as_mut().split(amount));
+                Some(block.as_memory())
+            }
+        })
+    }
+
+    // like allocate, but carve the memory from the end of this block.
+    pub fn allocate_high(&self, amount: usize) -> Option<Memory<'heap>> {
+        let s = self.as_mut();
+        s.ptr.and_then(|block| {
+            block.check_canary();
+            if amount > block.size {
+                None
+            } else if block.size - amount < FREE_BLOCK_SIZE {
+                s.ptr = block.next.ptr;
+                Some(block.as_memory())
+            } else {
+                let (_, a2) = block.as_memory().split_at(block.size - amount);
+                block.as_mut().set_size(block.size - amount);
+                Some(a2)
             }
         })
     }
@@ -64,7 +83,7 @@ impl<'heap> FreeBlockPtr<'heap> {
             Some(block) => {
                 if block.end() == m.start() {
                     // merge to the end of this block.
-                    block.as_mut().size += m.len();
+                    block.as_mut().set_size(block.size + m.len());
                     block.as_mut().check_merge_next();
                     None
                 } else {
@@ -98,11 +117,21 @@ pub struct FreeBlock<'heap> {
 
 pub const FREE_BLOCK_SIZE: usize = mem::size_of::<FreeBlock>();
 
+// with the `canary` feature, the word just past the header holds `size` xor
+// this pattern, so a stray write into free memory is caught before the block
+// is handed out. a block too small to have that word isn't checked.
+#[cfg(feature = "canary")]
+const CANARY: usize = usize::MAX / 0xff * 0xa5;
+
+// how much of the start of a free block the free list may write to: the
+// header, and the canary if there is one.
+pub const FREE_HEADER_SIZE: usize = FREE_BLOCK_SIZE + if cfg!(feature = "canary") { mem::size_of::<usize>() } else { 0 };
+
 impl<'heap> FreeBlock<'heap> {
     pub fn from_memory(m: Memory<'heap>, next: FreeBlockPtr<'heap>) -> &'heap mut FreeBlock<'heap> {
         let block = unsafe { &mut *(m.start() as *mut u8 as *mut FreeBlock) };
         block.next = next;
-        block.size = m.len();
+        block.set_size(m.len());
         block
     }
 
@@ -110,6 +139,29 @@ impl<'heap> FreeBlock<'heap> {
         Memory::new(unsafe { slice::from_raw_parts_mut(self.start() as *mut u8, self.size) })
     }
 
+    // every change to `size` goes through here, to keep the canary in sync.
+    #[inline]
+    fn set_size(&mut self, size: usize) {
+        self.size = size;
+        #[cfg(feature = "canary")]
+        if let Some(p) = self.canary_slot() { unsafe { *p = size ^ CANARY } }
+    }
+
+    // with the `canary` feature, panic if this block has been overwritten.
+    #[inline]
+    pub fn check_canary(&self) {
+        #[cfg(feature = "canary")]
+        if let Some(p) = self.canary_slot() {
+            assert!(unsafe { *p } == self.size ^ CANARY, "free list corrupted: free block at {:?} was overwritten", self.start());
+        }
+    }
+
+    #[cfg(feature = "canary")]
+    fn canary_slot(&self) -> Option<*mut usize> {
+        if self.size < FREE_BLOCK_SIZE + mem::size_of::<usize>() { return None }
+        Some(((self.start() as usize) + FREE_BLOCK_SIZE) as *mut usize)
+    }
+
     // for internal mutations only
     fn as_mut(&self) -> &mut FreeBlock {
         unsafe { &mut *(self as *const FreeBlock as *mut FreeBlock) }
@@ -125,14 +177,31 @@ impl<'heap> FreeBlock<'heap> {
         ((self.start() as usize) + self.size) as *mut u8
     }
 
+    // split this block in two at `amount`, linking the tail in as a new
+    // free block right after this one. both halves must be big enough to
+    // hold a FreeBlock.
+    pub fn split(&mut self, amount: usize) -> &'heap FreeBlock<'heap> {
+        assert!(amount >= FREE_BLOCK_SIZE);
+        assert!(self.size >= amount + FREE_BLOCK_SIZE);
+        let (_, tail) = self.as_memory().split_at(amount);
+        let next = FreeBlock::from_memory(tail, self.next);
+        self.set_size(amount);
+        self.next = FreeBlockPtr { ptr: Some(next) };
+        next
+    }
+
     // check if this block and the next can be merged, and if so, merge them.
-    pub fn check_merge_next(&mut self) {
-        self.next.ptr.map(|next| {
+    // returns true if they were merged.
+    pub fn check_merge_next(&mut self) -> bool {
+        if let Some(next) = self.next.ptr {
             if self.end() == next.start() {
-                self.size += next.size;
+                // the canary can land on the next block's header, so read it first.
                 self.next = next.next;
+                self.set_size(self.size + next.size);
+                return true
             }
-        });
+        }
+        false
     }
 }
 
@@ -174,9 +243,15 @@ impl<'a> FreeListSpan<'a> {
     }
 
     // if you know for sure the memory will slot between these two free
-    // blocks (in this span), we can do it O(1).
-    pub fn insert(&self, m: Memory<'a>) {
-        assert!(self.insert_point.try_insert_after(m).and_then(|m| self.ptr.try_insert_before(m)).is_none());
+    // blocks (in this span), we can do it O(1). if it doesn't fit here
+    // after all, the memory is handed back.
+    pub fn insert(&self, m: Memory<'a>) -> Result<(), Memory<'a>> {
+        // past the last free block, `ptr` is the end of the list, and
+        // `try_insert` will append to it.
+        match self.insert_point.try_insert_after(m).and_then(|m| self.ptr.try_insert(m)) {
+            None => Ok(()),
+            Some(m) => Err(m),
+        }
     }
 
     // you can traverse the free list as if this was an iterator.
@@ -218,6 +293,10 @@ impl<'heap> FreeList<'heap> {
         FreeList { list: FreeBlockPtr::new(m, LAST) }
     }
 
+    pub fn empty() -> FreeList<'heap> {
+        FreeList { list: LAST }
+    }
+
     pub fn iter(&self) -> FreeListIterator {
         FreeListIterator { next: &self.list }
     }
@@ -240,20 +319,91 @@ impl<'heap> FreeList<'heap> {
         self.iter_span().find_map(|p| p.ptr.allocate(amount))
     }
 
+    // allocate from the first block that has room for `amount` bytes at an
+    // address that's a multiple of `align`. blocks are carved in units of
+    // `granule`, so any leading gap is split off as its own free block.
+    // like `allocate`, a gap or tail too small to be a free block is never
+    // left behind: a small gap is skipped, and a small tail is absorbed.
+    pub fn allocate_aligned(&mut self, amount: usize, align: usize, granule: usize) -> Option<Memory<'heap>> {
+        self.iter_span().find_map(|span| {
+            let block = span.ptr.ptr?;
+            block.check_canary();
+            let (start, end) = (block.start() as usize, block.end() as usize);
+            let mut p = start;
+            // if the granule is a multiple of the alignment, moving along won't help.
+            if align <= granule && p % align != 0 { return None }
+            while (p % align != 0 || (p > start && p - start < FREE_BLOCK_SIZE)) && p < end { p += granule }
+            if p + amount > end { return None }
+            if p == start {
+                span.ptr.allocate(amount)
+            } else if end - p - amount < FREE_BLOCK_SIZE {
+                let (_, m) = block.as_memory().split_at(p - start);
+                block.as_mut().set_size(p - start);
+                Some(m)
+            } else {
+                block.as_mut().split(p - start);
+                block.next.allocate(amount)
+            }
+        })
+    }
+
+    // allocate from the end of the last block that's big enough.
+    pub fn allocate_high(&mut self, amount: usize) -> Option<Memory<'heap>> {
+        self.iter_span().filter(|p| p.ptr.ptr.map(|b| b.size >= amount).unwrap_or(false)).last()
+            .and_then(|p| p.ptr.allocate_high(amount))
+    }
+
     pub fn retire(&mut self, m: Memory<'heap>) {
-        // try_insert will return the memory if it won't fit here, so we
-        // do some ✨shenanigans✨ to move the memory thru an option, so
-        // rust will be satisfied.
-        let mut mm = Some(m);
-        assert!(self.iter_span().any(|span| {
-            mm = span.ptr.try_insert(mm.take().unwrap());
-            mm.is_none()
-        }));
+        assert!(self.try_retire(m).is_ok(), "free list is damaged");
+    }
+
+    // like `retire`, but if the list is damaged (out of order, or with
+    // nowhere to put the memory), or the memory overlaps a block that's
+    // already free, hand the memory back instead of panicking. only the
+    // part of the list walked to find the insert point is checked.
+    pub fn try_retire(&mut self, m: Memory<'heap>) -> Result<(), Memory<'heap>> {
+        let overlaps = |block: &FreeBlock, m: &Memory| block.start() < m.end() && m.start() < block.end();
+        let mut m = m;
+        for span in self.iter_span() {
+            if let Some(block) = span.ptr.ptr {
+                // an insert here can merge with the next block too, so check it as well.
+                if overlaps(block, &m) { return Err(m) }
+                if let Some(next) = block.next.ptr {
+                    if next.start() < block.end() || overlaps(next, &m) { return Err(m) }
+                }
+            }
+            match span.ptr.try_insert(m) {
+                None => return Ok(()),
+                Some(rest) => m = rest,
+            }
+        }
+        Err(m)
+    }
+
+    // merge every pair of adjacent free blocks, returning the number of
+    // merges. the list is sorted, so one pass is enough.
+    pub fn coalesce(&mut self) -> usize {
+        let mut merges = 0;
+        for block in self.iter() {
+            while block.as_mut().check_merge_next() { merges += 1 }
+        }
+        merges
     }
 
     pub fn bytes(&self) -> usize {
         self.iter().map(|b| b.size).sum()
     }
+
+    // bytes that could actually be handed out, if allocations are made in
+    // units of `granularity`: any tail smaller than that is a lost cause.
+    pub fn usable_bytes(&self, granularity: usize) -> usize {
+        self.iter().map(|b| floor_to(b.size, granularity)).sum()
+    }
+
+    // size of the biggest single free block
+    pub fn largest(&self) -> usize {
+        self.iter().map(|b| b.size).max().unwrap_or(0)
+    }
 }
 
 impl<'heap> fmt::Debug for FreeList<'heap> {
@@ -272,7 +422,8 @@ impl<'heap> fmt::Debug for FreeList<'heap> {
 
 #[cfg(test)]
 mod tests {
-    use super::{FreeList, Memory};
+    use crate::ceil_to;
+    use super::{FreeBlockPtr, FreeList, FREE_BLOCK_SIZE, LAST, Memory};
 
     fn assert_chain(f: &FreeList, expected: &[usize]) {
         let mut i = 0;
@@ -387,4 +538,153 @@ mod tests {
         assert_span_chain(&f, &[ 256, 0 ]);
         assert_eq!(f.first_available(), origin);
     }
+
+    #[test]
+    fn coalesce() {
+        let mut data: [u8; 256] = [0; 256];
+        let (m1, m2) = Memory::new(&mut data).split_at(64);
+        let (m2, m3) = m2.split_at(32);
+        let (m3, m4) = m3.split_at(32);
+        let (_, m5) = m4.split_at(32);
+
+        // build a chain of adjacent blocks by hand, since retire would merge them.
+        let chain = FreeBlockPtr::new(m1, FreeBlockPtr::new(m2, FreeBlockPtr::new(m3, FreeBlockPtr::new(m5, LAST))));
+        let mut f = FreeList { list: chain };
+        assert_chain(&f, &[ 64, 32, 32, 96 ]);
+        assert_eq!(f.coalesce(), 2);
+        assert_chain(&f, &[ 128, 96 ]);
+        assert_eq!(f.coalesce(), 0);
+    }
+
+    #[test]
+    fn retire_corrupt() {
+        let mut data: [u8; 256] = [0; 256];
+        let (m1, m2) = Memory::new(&mut data).split_at(64);
+        let (m2, m3) = m2.split_at(64);
+
+        // a list that's out of order can't be trusted to take more memory.
+        let chain = FreeBlockPtr::new(m3, FreeBlockPtr::new(m1, LAST));
+        let mut f = FreeList { list: chain };
+        let m2 = f.try_retire(m2).err().unwrap();
+        assert_eq!(m2.len(), 64);
+        assert_chain(&f, &[ 128, 64 ]);
+    }
+
+    #[test]
+    fn retire_overlapping() {
+        let mut data: [u8; 256] = [0; 256];
+        let (m1, m2) = Memory::new(&mut data).split_at(64);
+        let (m2, m3) = m2.split_at(64);
+        let (start, end) = (m1.start(), m2.end());
+        let mut f = FreeList { list: FreeBlockPtr::new(m1, FreeBlockPtr::new(m3, LAST)) };
+
+        // memory that's partly free already is handed back.
+        assert!(f.try_retire(Memory::from_addresses(start, end)).is_err());
+        assert_chain(&f, &[ 64, 128 ]);
+        f.retire(m2);
+        assert_chain(&f, &[ 256 ]);
+    }
+
+    #[test]
+    fn split() {
+        let mut data: [u8; 256] = [0; 256];
+        let f = FreeList::new(Memory::new(&mut data));
+        let tail = f.list.ptr.unwrap().as_mut().split(64);
+        assert_eq!(tail.size, 192);
+        assert_eq!(tail.start(), f.first_available().wrapping_add(64));
+        assert_chain(&f, &[ 64, 192 ]);
+    }
+
+    #[test]
+    fn usable_bytes() {
+        let mut data: [u8; 256] = [0; 256];
+        let (m1, m2) = Memory::new(&mut data).split_at(24);
+        let (_, m3) = m2.split_at(24);
+        let (m3, m4) = m3.split_at(24);
+        let (_, m5) = m4.split_at(24);
+        let chain = FreeBlockPtr::new(m1, FreeBlockPtr::new(m3, FreeBlockPtr::new(m5, LAST)));
+        let f = FreeList { list: chain };
+        assert_chain(&f, &[ 24, 24, 160 ]);
+        assert_eq!(f.bytes(), 208);
+        assert_eq!(f.usable_bytes(16), 192);
+        assert_eq!(f.usable_bytes(8), 208);
+    }
+
+    #[test]
+    fn allocate_high() {
+        let mut data: [u8; 256] = [0; 256];
+        let start = &mut data[0] as *mut u8;
+        let mut f = FreeList::new(Memory::new(&mut data));
+        let m1 = f.allocate_high(64).unwrap();
+        assert_eq!(m1.start(), start.wrapping_add(192));
+        assert_eq!(m1.len(), 64);
+        assert_chain(&f, &[ 192 ]);
+
+        let m2 = f.allocate(64).unwrap();
+        assert_eq!(m2.start(), start);
+        f.retire(m1);
+        assert_chain(&f, &[ 192 ]);
+
+        // picks the last block that fits, and uses all of a block if the leftover would be too small.
+        let _m3 = f.allocate(32).unwrap();
+        let _m4 = f.allocate_high(32).unwrap();
+        f.retire(m2);
+        assert_chain(&f, &[ 64, 128 ]);
+        let m5 = f.allocate_high(120).unwrap();
+        assert_eq!(m5.len(), 128);
+        assert_chain(&f, &[ 64 ]);
+    }
+
+    #[test]
+    fn allocate_aligned() {
+        #[repr(align(1024))]
+        struct Aligned([u8; 512]);
+        let mut data = Aligned([0; 512]);
+        let start = &mut data.0[0] as *mut u8;
+        let mut f = FreeList::new(Memory::new(&mut data.0));
+        f.allocate(16).unwrap();
+        let m1 = f.allocate_aligned(32, 16, 16).unwrap();
+        assert_eq!(m1.start(), start.wrapping_add(16));
+        assert_chain(&f, &[ 464 ]);
+
+        // the gap is split off as a free block.
+        let m2 = f.allocate_aligned(32, 128, 16).unwrap();
+        assert_eq!(m2.start(), start.wrapping_add(128));
+        assert_eq!(m2.len(), 32);
+        assert_chain(&f, &[ 80, 352 ]);
+        assert!(f.allocate_aligned(32, 1024, 16).is_none());
+    }
+
+    #[test]
+    fn no_tiny_remainder() {
+        #[repr(align(1024))]
+        struct Aligned([u8; 512]);
+        let mut data = Aligned([0; 512]);
+        let start = &mut data.0[0] as *mut u8;
+        let mut f = FreeList::new(Memory::new(&mut data.0));
+
+        // leaving FREE_BLOCK_SIZE - 1 bytes behind would be useless, so the
+        // whole block is used.
+        let m1 = f.allocate(512 - (FREE_BLOCK_SIZE - 1)).unwrap();
+        assert_eq!(m1.len(), 512);
+        assert_chain(&f, &[]);
+        f.retire(m1);
+        let m2 = f.allocate_high(512 - (FREE_BLOCK_SIZE - 1)).unwrap();
+        assert_eq!(m2.len(), 512);
+        assert_chain(&f, &[]);
+        f.retire(m2);
+        let m3 = f.allocate(128).unwrap();
+        let m4 = f.allocate_aligned(256 - (FREE_BLOCK_SIZE - 1), 256, 1).unwrap();
+        assert_eq!(m4.start(), start.wrapping_add(256));
+        assert_eq!(m4.len(), 256);
+        assert_chain(&f, &[ 128 ]);
+        f.retire(m3);
+        f.retire(m4);
+
+        // a leading gap too small to be a free block is skipped over.
+        f.allocate(24).unwrap();
+        let m5 = f.allocate_aligned(16, 16, 8).unwrap();
+        assert_eq!(m5.start(), start.wrapping_add(ceil_to(24 + FREE_BLOCK_SIZE, 16)));
+        assert!(f.iter().all(|b| b.size >= FREE_BLOCK_SIZE));
+    }
 }
@@ -0,0 +1,39 @@
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::{slice, str};
+
+/// A string allocated in a [`Heap`](struct.Heap.html) by
+/// [`allocate_gc_str`](struct.Heap.html#method.allocate_gc_str): a length
+/// and a pointer to the string's bytes, which follow immediately in the
+/// same allocation. A `&GcStr` can be passed as a GC root, and keeps the
+/// whole string alive.
+#[repr(C)]
+pub struct GcStr {
+    len: usize,
+    data: *const u8,
+}
+
+impl GcStr {
+    // `data` must point to `len` bytes of UTF-8 that live as long as this does.
+    pub(crate) fn new(len: usize, data: *const u8) -> GcStr {
+        GcStr { len, data }
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(slice::from_raw_parts(self.data, self.len)) }
+    }
+}
+
+impl Default for GcStr {
+    fn default() -> GcStr {
+        GcStr { len: 0, data: NonNull::dangling().as_ptr() }
+    }
+}
+
+impl Deref for GcStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
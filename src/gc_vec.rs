@@ -0,0 +1,121 @@
+use core::marker::PhantomData;
+use core::ops::{Index, IndexMut};
+use core::{mem, ptr, slice};
+
+use crate::heap::Heap;
+
+/// A growable array whose elements live in a [`Heap`](struct.Heap.html).
+///
+/// The `GcVec` itself is just a header (pointer, length, and capacity), so
+/// to keep its elements alive across a GC, allocate the header in the heap
+/// too (with `allocate_object::<GcVec<T>>()`) and make it reachable from
+/// your roots. The conservative scanner will follow the header's pointer to
+/// the elements.
+///
+/// When the array runs out of room, `push` allocates a buffer twice as big,
+/// copies the elements over, and retires the old buffer. Like everything
+/// else in the heap, elements are never dropped.
+pub struct GcVec<'heap, T> {
+    ptr: *mut T,
+    len: usize,
+    capacity: usize,
+    _heap: PhantomData<&'heap mut T>,
+}
+
+impl<'heap, T> Default for GcVec<'heap, T> {
+    fn default() -> GcVec<'heap, T> {
+        GcVec::new()
+    }
+}
+
+impl<'heap, T> GcVec<'heap, T> {
+    /// Create an empty array. Nothing is allocated until the first `push`.
+    pub fn new() -> GcVec<'heap, T> {
+        GcVec { ptr: ptr::null_mut(), len: 0, capacity: 0, _heap: PhantomData }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How many elements fit before the next `push` will need to grow the
+    /// buffer. Because allocations are rounded up to the block size, this
+    /// may be a bit more than was asked for.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Add an element to the end of the array, growing it if necessary.
+    /// If the heap doesn't have room to grow it, the element is handed back.
+    pub fn push<const BLOCK: usize>(&mut self, heap: &mut Heap<'heap, BLOCK>, value: T) -> Result<(), T> {
+        assert!(mem::size_of::<T>() > 0);
+        if self.len == self.capacity && !self.grow(heap) { return Err(value) }
+        unsafe { ptr::write(self.ptr.add(self.len), value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove the last element and return it, or `None` if it's empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 { return None }
+        self.len -= 1;
+        Some(unsafe { ptr::read(self.ptr.add(self.len)) })
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        if self.ptr.is_null() { return &[] }
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.ptr.is_null() { return &mut [] }
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// Address of the element buffer (null if nothing has been allocated).
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr
+    }
+
+    // double the capacity (or start at 1), by allocating a new buffer and
+    // copying the elements over.
+    fn grow<const BLOCK: usize>(&mut self, heap: &mut Heap<'heap, BLOCK>) -> bool {
+        let size = mem::size_of::<T>();
+        let wanted = if self.capacity == 0 { 1 } else { self.capacity * 2 };
+        let m = match heap.allocate(wanted * size) {
+            Some(m) => m,
+            None => return false,
+        };
+        let new_ptr = m.start() as *mut T;
+        if !self.ptr.is_null() {
+            unsafe {
+                ptr::copy_nonoverlapping(self.ptr, new_ptr, self.len);
+                heap.retire_object(&mut *self.ptr);
+            }
+        }
+        self.ptr = new_ptr;
+        self.capacity = heap.size_of_ptr(new_ptr) / size;
+        true
+    }
+}
+
+impl<'heap, T> Index<usize> for GcVec<'heap, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.as_slice()[index]
+    }
+}
+
+impl<'heap, T> IndexMut<usize> for GcVec<'heap, T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.as_mut_slice()[index]
+    }
+}
@@ -1,70 +1,106 @@
-use core::{fmt, mem, ptr, slice};
+use core::{cmp, fmt, mem, ptr, slice, str};
+use core::ops::{Deref, DerefMut};
 
-use crate::{BLOCK_SIZE_BYTES, ceil_to, div_ceil, floor_to};
+use crate::{BLOCK_SIZE_BYTES, MIN_BLOCK_SIZE, ceil_to, div_ceil, floor_to};
 use crate::color_map::{BlockRange, BLOCKS_PER_COLORMAP_BYTE, Color, ColorMap};
-use crate::free_list::{FreeBlock, FreeList, FreeListSpan};
+use crate::free_list::{FREE_HEADER_SIZE, FreeBlock, FreeList, FreeListSpan};
+use crate::gc_str::GcStr;
 use crate::memory::Memory;
+#[cfg(feature = "observer")]
+use crate::observer::GcObserver;
 use crate::string_buffer::StringBuffer;
+use crate::trace::Marker;
 
 
+/// What's in a [`Span`](struct.Span.html) of the heap: either free memory,
+/// or a live allocation marked with its current GC color.
 #[derive(Clone, Copy, PartialEq)]
-enum SpanType {
-    Color(Color),
-    Free
+pub enum SpanKind {
+    Free,
+    Live(Color),
 }
 
-impl fmt::Debug for SpanType {
+impl fmt::Debug for SpanKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SpanType::Free => write!(f, "FREE"),
-            SpanType::Color(color) => write!(f, "{:?}", color),
+            SpanKind::Free => write!(f, "FREE"),
+            SpanKind::Live(color) => write!(f, "{:?}", color),
         }
     }
 }
 
 
+/// A contiguous span of the heap, as yielded by
+/// [`Heap::spans`](struct.Heap.html#method.spans).
+#[derive(Clone, Copy, PartialEq)]
+pub struct Span {
+    /// address of the first byte of the span
+    pub start: *const u8,
+
+    /// size of the span, in bytes
+    pub len: usize,
+
+    /// whether the span is free or allocated
+    pub kind: SpanKind,
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}[{}]", self.kind, self.len)
+    }
+}
+
+
 #[derive(Clone, Copy)]
 struct HeapSpan<'a> {
     pub start: *mut u8,
     pub end: *mut u8,
-    pub span_type: SpanType,
+    pub span_kind: SpanKind,
     pub free_list_span: FreeListSpan<'a>,
 }
 
 impl<'a> HeapSpan<'a> {
     fn from_free_block(block: &'a FreeBlock, free_list_span: FreeListSpan<'a>) -> HeapSpan<'a> {
-        HeapSpan { start: block.start(), end: block.end(), span_type: SpanType::Free, free_list_span }
+        HeapSpan { start: block.start(), end: block.end(), span_kind: SpanKind::Free, free_list_span }
     }
 
-    fn from_block_range(heap: &Heap, range: BlockRange, free_list_span: FreeListSpan<'a>) -> HeapSpan<'a> {
+    fn from_block_range<const BLOCK: usize>(heap: &Heap<'_, BLOCK>, range: BlockRange, free_list_span: FreeListSpan<'a>) -> HeapSpan<'a> {
         HeapSpan {
             start: heap.address_of(range.start),
             end: heap.address_of(range.end),
-            span_type: SpanType::Color(range.color),
+            span_kind: SpanKind::Live(range.color),
             free_list_span,
         }
     }
+
+    fn as_span(&self) -> Span {
+        Span {
+            start: self.start,
+            len: (self.end as usize) - (self.start as usize),
+            kind: self.span_kind,
+        }
+    }
 }
 
 impl<'a> fmt::Debug for HeapSpan<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if f.alternate() {
-            write!(f, "{:?}[{:?} - {:?}, between {:?} - {:?}]", self.span_type, self.start, self.end, self.free_list_span.insert_point, self.free_list_span.ptr)
+            write!(f, "{:?}[{:?} - {:?}, between {:?} - {:?}]", self.span_kind, self.start, self.end, self.free_list_span.insert_point, self.free_list_span.ptr)
         } else {
-            write!(f, "{:?}[{}]", self.span_type, (self.end as usize) - (self.start as usize))
+            write!(f, "{:?}", self.as_span())
         }
     }
 }
 
 
-struct HeapIterator<'a> {
-    heap: &'a Heap<'a>,
+struct HeapIterator<'a, const BLOCK: usize> {
+    heap: &'a Heap<'a, BLOCK>,
     free_list_span: FreeListSpan<'a>,
     current: *mut u8,
 }
 
-impl<'a> HeapIterator<'a> {
-    fn new(heap: &'a Heap) -> HeapIterator<'a> {
+impl<'a, const BLOCK: usize> HeapIterator<'a, BLOCK> {
+    fn new(heap: &'a Heap<'a, BLOCK>) -> HeapIterator<'a, BLOCK> {
         // there is always at least one span, because the final null pointer
         // is yielded. we stop calling `next()` at that point, so we never
         // get to the `None` end of the iteration.
@@ -73,7 +109,7 @@ impl<'a> HeapIterator<'a> {
     }
 }
 
-impl<'a> Iterator for HeapIterator<'a> {
+impl<'a, const BLOCK: usize> Iterator for HeapIterator<'a, BLOCK> {
     type Item = HeapSpan<'a>;
 
     // tricky: there are two lists to traverse in tandem. if the current
@@ -100,14 +136,193 @@ impl<'a> Iterator for HeapIterator<'a> {
             }
         }
 
-        let span = self.heap.get_range(self.current);
+        // the previous span ended here, so this is the start of a span.
+        let block = ((self.current as usize) - (self.heap.start as usize)) / BLOCK;
+        let span = self.heap.get_range_from_start(block);
         self.current = self.heap.address_of(span.end);
         Some(HeapSpan::from_block_range(self.heap, span, self.free_list_span))
     }
 }
 
 
+/// A compact handle to an object allocated in a heap, as returned by
+/// [`Heap::handle_of`](struct.Heap.html#method.handle_of). It stores the
+/// object's block index, so it's only 32 bits even on 64-bit systems, and
+/// stays valid for as long as the object is alive (objects never move).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeapRef(pub u32);
+
+
+/// Iterator over the [`Span`](struct.Span.html)s of a heap, in address
+/// order. You can get one from
+/// [`Heap::spans`](struct.Heap.html#method.spans), or by iterating over a
+/// `&Heap`:
+///
+/// ```rust
+/// use mwgc::{Heap, SpanKind};
+///
+/// let mut data: [u8; 256] = [0; 256];
+/// let mut h = Heap::from_bytes(&mut data);
+/// h.allocate(32).unwrap();
+/// h.allocate(64).unwrap();
+///
+/// let mut live = 0;
+/// for span in &h {
+///     if let SpanKind::Live(_) = span.kind { live += span.len }
+/// }
+/// assert_eq!(live, 96);
+/// ```
+pub struct Spans<'a, const BLOCK: usize = BLOCK_SIZE_BYTES>(HeapIterator<'a, BLOCK>);
+
+impl<'a, const BLOCK: usize> Iterator for Spans<'a, BLOCK> {
+    type Item = Span;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|span| span.as_span())
+    }
+}
+
+
+/// A span of memory reserved from a heap by
+/// [`Heap::bump_region`](struct.Heap.html#method.bump_region), to be handed
+/// out in order as small objects that sit next to each other. Each object
+/// is a separate allocation, so the GC can free them individually. Any
+/// memory that wasn't handed out is returned to the heap when the region
+/// is dropped (or [`seal`](struct.BumpRegion.html#method.seal)ed).
+pub struct BumpRegion<'a, 'heap, const BLOCK: usize = BLOCK_SIZE_BYTES> {
+    heap: &'a mut Heap<'heap, BLOCK>,
+    next: *mut u8,
+    end: *mut u8,
+}
+
+impl<'a, 'heap, const BLOCK: usize> BumpRegion<'a, 'heap, BLOCK> {
+    /// Allocate the next object of type `T` from the region, initialized to
+    /// its default value. Returns `None` if the region is full.
+    pub fn alloc<T: Default>(&mut self) -> Option<&'heap mut T> {
+        let amount = self.heap.actual_size(mem::size_of::<T>());
+        if (self.end as usize) - (self.next as usize) < amount { return None }
+        let m = Memory::from_addresses(self.next, ((self.next as usize) + amount) as *mut u8);
+        self.next = m.end();
+        let m = self.heap.claim(m);
+        let obj = m.inner().as_mut_ptr() as *mut T;
+        unsafe {
+            ptr::write(obj, T::default());
+            Some(&mut *obj)
+        }
+    }
+
+    /// Bytes left in the region.
+    pub fn remaining(&self) -> usize {
+        (self.end as usize) - (self.next as usize)
+    }
+
+    /// Stop allocating from this region, and give the unused memory back to
+    /// the heap.
+    pub fn seal(self) {}
+}
+
+impl<'a, 'heap, const BLOCK: usize> Drop for BumpRegion<'a, 'heap, BLOCK> {
+    fn drop(&mut self) {
+        if self.next < self.end {
+            // it was never colored, so it's still marked as free.
+            self.heap.free_list.retire(Memory::from_addresses(self.next, self.end));
+        }
+    }
+}
+
+
+/// An object allocated by
+/// [`Heap::alloc_scoped`](struct.Heap.html#method.alloc_scoped), which is
+/// retired as soon as this guard is dropped, without waiting for a GC. Like
+/// every object in the heap, its own `Drop` is never run.
+pub struct ScopedObj<'a, 'heap, T: 'heap, const BLOCK: usize = BLOCK_SIZE_BYTES> {
+    heap: &'a mut Heap<'heap, BLOCK>,
+    obj: *mut T,
+}
+
+impl<'a, 'heap, T: 'heap, const BLOCK: usize> Deref for ScopedObj<'a, 'heap, T, BLOCK> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.obj }
+    }
+}
+
+impl<'a, 'heap, T: 'heap, const BLOCK: usize> DerefMut for ScopedObj<'a, 'heap, T, BLOCK> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.obj }
+    }
+}
+
+impl<'a, 'heap, T: 'heap, const BLOCK: usize> Drop for ScopedObj<'a, 'heap, T, BLOCK> {
+    fn drop(&mut self) {
+        self.heap.retire_object(unsafe { &mut *self.obj });
+    }
+}
+
+
+/// With the `fuzzing` feature: one step of a random workload, for
+/// [`Heap::apply_op`](struct.Heap.html#method.apply_op). A fuzzer can
+/// generate a stream of these, and check
+/// [`Heap::validate`](struct.Heap.html#method.validate) after each one.
+#[cfg(feature = "fuzzing")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeapOp {
+    /// Allocate this many bytes, and remember the allocation in the first
+    /// empty slot (of 16). Nothing happens if the slots are full.
+    Alloc(usize),
+
+    /// Retire the allocation in this slot (modulo 16), if there is one.
+    Retire(usize),
+
+    /// Run a full GC, with every remembered allocation as a root.
+    Gc,
+
+    /// Merge adjacent free spans.
+    Coalesce,
+}
+
+
+/// Errors from the fallible (`try_`) versions of the heap's operations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcError {
+    /// The call was made at the wrong point in a collection: for example,
+    /// calling `sweep` before marking is finished.
+    WrongPhase,
+
+    /// The memory being freed isn't inside this heap.
+    ForeignMemory,
+
+    /// The free list is damaged, so memory couldn't be returned to it.
+    CorruptFreeList,
+
+    /// A scratch buffer passed to `estimate_garbage` is too small for the
+    /// heap.
+    ScratchTooSmall,
+
+    /// The range of memory waiting to be checked by the mark phase is
+    /// damaged (it ends before it starts).
+    CorruptCheckSpan,
+
+    /// The memory being freed is already free.
+    AlreadyFree,
+}
+
+
+/// Errors from [`Heap::try_from_bytes`](struct.Heap.html#method.try_from_bytes).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeapInitError {
+    /// The memory doesn't start on a multiple of the block size, so objects
+    /// allocated from it wouldn't be aligned.
+    Unaligned,
+
+    /// The memory is too small to hold even one block.
+    TooSmall,
+}
+
+
 /// Stats returned from [`Heap::get_stats`](struct.Heap.html#method.get_stats).
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct HeapStats {
     /// total bytes available in the heap: provided memory minus overhead
     pub total_bytes: usize,
@@ -115,6 +330,27 @@ pub struct HeapStats {
     /// bytes free for future allocations right now
     pub free_bytes: usize,
 
+    /// bytes of the provided memory that can't be allocated: the color map,
+    /// plus any slack left over from rounding the pool to whole blocks
+    pub overhead_bytes: usize,
+
+    /// with the `profiling` feature: the most bytes that have ever been
+    /// allocated at once
+    #[cfg(feature = "profiling")]
+    pub peak_live_bytes: usize,
+
+    /// with the `profiling` feature: how many objects were marked as live by
+    /// the most recent (or current) mark phase. Each object is counted once,
+    /// no matter how many references to it were found.
+    #[cfg(feature = "profiling")]
+    pub marked_objects: usize,
+
+    /// with the `profiling` feature: how many times a byte of the color map
+    /// has been written. Each byte holds the colors of 4 blocks, so small
+    /// allocations next to each other keep rewriting the same byte.
+    #[cfg(feature = "profiling")]
+    pub colormap_writes: usize,
+
     /// for testing & debugging: the extent of the pool
     pub start: *const u8,
 
@@ -123,6 +359,22 @@ pub struct HeapStats {
 }
 
 
+// how many objects can be pinned as permanent roots.
+#[cfg(feature = "pinning")]
+const PIN_SLOTS: usize = 4;
+
+// how many objects can have a shorter scan length.
+#[cfg(feature = "scan_limits")]
+const SCAN_LIMIT_SLOTS: usize = 4;
+
+// how many allocations `apply_op` can keep track of.
+#[cfg(feature = "fuzzing")]
+const FUZZ_SLOTS: usize = 16;
+
+// allocation sizes tracked by the profiling histogram: 1, 2, 3, 4, and 5+ blocks.
+#[cfg(feature = "profiling")]
+const HISTOGRAM_BUCKETS: usize = 5;
+
 #[derive(PartialEq)]
 enum Phase {
     QUIET, MARKING, MARKED
@@ -132,8 +384,10 @@ enum Phase {
 /// chunks of it, and garbage collects unused chunks on demand.
 ///
 /// The heap is organized into allocatable blocks, with a small region
-/// reserved as a bitmap. The block size is configured by a compile-time
-/// constant, using a default of 16 bytes. Each block has a 2-bit "color"
+/// reserved as a bitmap. The block size is the `BLOCK` parameter, with a
+/// default of 16 bytes: smaller blocks waste less memory on rounding, and
+/// larger blocks need a smaller bitmap. It must be a power of 2, and big
+/// enough to hold a free-list link (below). Each block has a 2-bit "color"
 /// in the bitmap, at a cost of about 2% overhead. One "color" is used to
 /// mark continuations of contiguous spans, so each allocation can be
 /// identified by a color followed by one or more "continue" markers. The
@@ -142,12 +396,28 @@ enum Phase {
 /// "check".
 ///
 /// Separately, a sorted free list is maintained by storing a "next" link
-/// and size in each free block. This consumes 8 bytes on a 32-bit system,
-/// limiting the minimum block size.
+/// and size in each free block. This consumes 8 bytes on a 32-bit system
+/// (or 4 bytes on a 16-bit system), limiting the minimum block size to
+/// [`MIN_BLOCK_SIZE`](constant.MIN_BLOCK_SIZE.html).
 ///
-/// The heap object (its state) should consume about 9 words, or 36 bytes
-/// on a 32-bit system.
-pub struct Heap<'heap> {
+/// Allocation and stats never panic (except with the `canary` feature, if
+/// a free block has been overwritten). The GC calls (`mark_start`,
+/// `mark_round`, `sweep`, and `gc`) panic if they're called out of order,
+/// or if the free list or marking state has been damaged, and `retire`
+/// panics if it's given memory from outside the heap or memory that's
+/// already free, or the free list has been damaged. Each of these has a
+/// `try_` version that returns a [`GcError`](enum.GcError.html) instead.
+/// The remaining calls that can panic are `new` (if the memory is too small
+/// to hold a heap), `retire_object` and `retire_array` (for memory outside
+/// the heap, memory that's already free, a pointer into the middle of an
+/// allocation, or a damaged free list), `prefault` (for a page size of 0),
+/// and `mark_slice`, `add_mark_roots`, and `marker` (if called at the wrong
+/// point in a collection).
+///
+/// The heap object (its state) should consume about 14 words, or 56 bytes
+/// on a 32-bit system. Optional features like `observer` and `pinning` add
+/// more.
+pub struct Heap<'heap, const BLOCK: usize = BLOCK_SIZE_BYTES> {
     start: *mut u8,
     end: *mut u8,
     blocks: usize,
@@ -157,52 +427,354 @@ pub struct Heap<'heap> {
     // gc state:
     current_color: Color,
     phase: Phase,
+    zero_on_sweep: bool,
+    debug_fill: Option<(u8, u8)>,
 
     // for marking:
     check_start: *const u8,
     check_end: *const u8,
+    pointer_mask: usize,
+    mark_total_blocks: usize,
+    mark_done_blocks: usize,
+    #[cfg(feature = "profiling")]
+    marked_objects: usize,
+
+    // held as a pointer so the heap stays covariant over `'heap`; it came
+    // from a `&'heap mut`, so it's valid for as long as the heap is.
+    #[cfg(feature = "observer")]
+    observer: Option<ptr::NonNull<dyn GcObserver + 'heap>>,
+
+    // objects that are always roots (null if unused):
+    #[cfg(feature = "pinning")]
+    pins: [*const u8; PIN_SLOTS],
+
+    // objects that are only scanned up to a byte offset (null if unused):
+    #[cfg(feature = "scan_limits")]
+    scan_limits: [(*const u8, usize); SCAN_LIMIT_SLOTS],
+
+    // for stats:
+    overhead_bytes: usize,
+    live_bytes: usize,
+    #[cfg(feature = "profiling")]
+    peak_live_bytes: usize,
+    #[cfg(feature = "profiling")]
+    histogram: [usize; HISTOGRAM_BUCKETS],
+
+    // live allocations made by `apply_op` (null if unused):
+    #[cfg(feature = "fuzzing")]
+    fuzz_slots: [*const u8; FUZZ_SLOTS],
 }
 
 impl<'heap> Heap<'heap> {
-    /// Create a new heap out of a mutable chunk of memory.
+    /// Create a new heap out of a mutable chunk of memory. A small part at
+    /// the end is reserved for the color map, and the rest is used for
+    /// allocations.
+    ///
+    /// Panics if the memory is too small to hold even one block.
     pub fn new(m: Memory<'heap>) -> Heap<'heap> {
+        Self::with_block_size(m)
+    }
+
+    /// Create a new heap that allocates out of `pool`, but keeps its color
+    /// map in a separate chunk of memory. This is useful if some memory is
+    /// faster than the rest: the color map is used heavily during GC.
+    ///
+    /// The color map needs one byte for every 4 blocks of the pool. Returns
+    /// `None` if `colormap` is too small, `pool` can't hold even one block,
+    /// or the two overlap.
+    pub fn with_split_memory(pool: Memory<'heap>, colormap: Memory<'heap>) -> Option<Heap<'heap>> {
+        Self::from_split(pool, colormap)
+    }
+
+    /// Like [`new()`](struct.Heap.html#method.new), but also return the
+    /// number of bytes of `m` that are wasted: used by neither the pool nor
+    /// the color map, because the pool is rounded down to whole blocks.
+    pub fn new_with_waste(m: Memory<'heap>) -> (Heap<'heap>, usize) {
+        let heap = Self::new(m);
+        let waste = heap.overhead_bytes - heap.color_map.as_bytes().len();
+        (heap, waste)
+    }
+
+    /// For tests: create a new heap (like
+    /// [`new()`](struct.Heap.html#method.new)) with some memory already
+    /// allocated, to set up a particular pattern of fragmentation directly.
+    /// Each range is a `(start, end)` pair of block indexes, and the end is
+    /// exclusive. Every other block is free. Returns `None` if the ranges
+    /// are out of order, overlap, or run past the end of the heap.
+    pub fn from_spans(m: Memory<'heap>, live_ranges: &[(usize, usize)]) -> Option<Heap<'heap>> {
+        let mut heap = Self::new(m);
+        let mut last_end = 0;
+        for &(start, end) in live_ranges {
+            if start < last_end || start >= end || end > heap.blocks { return None }
+            last_end = end;
+        }
+
+        // take everything off the free list, then give back the gaps.
+        heap.free_list.allocate(heap.blocks * BLOCK_SIZE_BYTES);
+        let mut next = 0;
+        for &(start, end) in live_ranges.iter().chain([ (heap.blocks, heap.blocks) ].iter()) {
+            if next < start {
+                heap.free_list.retire(Memory::from_addresses(heap.address_of(next), heap.address_of(start)));
+            }
+            if start < end {
+                heap.claim(Memory::from_addresses(heap.address_of(start), heap.address_of(end)));
+            }
+            next = end;
+        }
+        Some(heap)
+    }
+
+    /// Create a new heap out of a mutable byte-slice.
+    pub fn from_bytes(bytes: &'heap mut [u8]) -> Heap<'heap> {
+        Self::new(Memory::new(bytes))
+    }
+
+    /// Like [`from_bytes()`](struct.Heap.html#method.from_bytes), but
+    /// returns an error instead of panicking if the buffer is too small, and
+    /// also rejects a buffer that isn't aligned to the block size. (A static
+    /// byte array can land at any address.)
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn try_from_bytes(bytes: &'heap mut [u8]) -> Result<Heap<'heap>, HeapInitError> {
+        if (bytes.as_ptr() as usize) % BLOCK_SIZE_BYTES != 0 { return Err(HeapInitError::Unaligned) }
+        if Self::block_count_for(bytes.len()) == 0 { return Err(HeapInitError::TooSmall) }
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// How many blocks would a heap made from a buffer of `buffer_len`
+    /// bytes have? This is a `const fn`, so static buffers can be sized at
+    /// compile time.
+    pub const fn block_count_for(buffer_len: usize) -> usize {
+        Self::geometry(buffer_len).0 / BLOCK_SIZE_BYTES
+    }
+
+    /// How many bytes could be allocated from a heap made from a buffer of
+    /// `buffer_len` bytes? This will be the heap's `total_bytes`.
+    pub const fn usable_bytes_for(buffer_len: usize) -> usize {
+        Self::geometry(buffer_len).0
+    }
+
+    /// How big a buffer is needed for a heap with at least `usable` bytes
+    /// available for allocation? For example:
+    ///
+    /// ```rust
+    /// use mwgc::Heap;
+    ///
+    /// static mut BUFFER: [u8; Heap::buffer_size_for(4096)] = [0; Heap::buffer_size_for(4096)];
+    /// assert_eq!(Heap::usable_bytes_for(Heap::buffer_size_for(4096)), 4096);
+    /// ```
+    pub const fn buffer_size_for(usable: usize) -> usize {
+        let blocks = div_ceil(usable, BLOCK_SIZE_BYTES);
+        blocks * BLOCK_SIZE_BYTES + div_ceil(blocks, BLOCKS_PER_COLORMAP_BYTE)
+    }
+}
+
+impl<'heap, const BLOCK: usize> Heap<'heap, BLOCK> {
+    // every block must be able to hold a free-list link, and block math
+    // assumes a power of two. checked for each block size that's used.
+    const BLOCK_SIZE_OK: () = assert!(
+        BLOCK >= MIN_BLOCK_SIZE && BLOCK.is_power_of_two(),
+        "block size must be a power of 2, and big enough to hold a free block"
+    );
+
+    // for a chunk of memory `len` bytes long, return the size of the pool
+    // (rounded down to a whole number of blocks) and of the color map.
+    const fn geometry(len: usize) -> (usize, usize) {
         // total heap = pool + color_map, and pool is just color_map_size * blocks_per_colormap_byte * block_size
         // so color_map_size = heap size / (1 + bpm * bs)
-        let divisor = 1 + BLOCKS_PER_COLORMAP_BYTE * BLOCK_SIZE_BYTES;
-        let color_map_size = div_ceil(m.len(), divisor);
-        let pool_size = floor_to(m.len() - color_map_size, BLOCK_SIZE_BYTES);
+        let divisor = 1 + BLOCKS_PER_COLORMAP_BYTE * BLOCK;
+        let color_map_size = div_ceil(len, divisor);
+        (floor_to(len - color_map_size, BLOCK), color_map_size)
+    }
+
+    // split a chunk of memory into (pool, color map).
+    fn layout(m: Memory<'heap>) -> (Memory<'heap>, Memory<'heap>) {
+        let (pool_size, color_map_size) = Self::geometry(m.len());
         let len = m.len();
         let (pool_data, color_data) = m.split_at(len - color_map_size);
-        let blocks = pool_size / BLOCK_SIZE_BYTES;
+        (pool_data.split_at(pool_size).0, color_data)
+    }
+
+    /// Create a new heap with a block size other than the default, out of a
+    /// mutable chunk of memory, like [`new()`](struct.Heap.html#method.new):
+    ///
+    /// ```rust
+    /// use mwgc::{Heap, Memory};
+    ///
+    /// let mut data: [u8; 256] = [0; 256];
+    /// let h = Heap::<32>::with_block_size(Memory::new(&mut data));
+    /// assert_eq!(h.get_stats().total_bytes, 224);
+    /// ```
+    ///
+    /// Panics if the memory is too small to hold even one block.
+    pub fn with_block_size(m: Memory<'heap>) -> Heap<'heap, BLOCK> {
+        let len = m.len();
+        let (pool, color_data) = Self::layout(m);
+        let mut heap = Self::from_split(pool, color_data).expect("heap memory is too small");
+        heap.overhead_bytes = len - heap.blocks * BLOCK;
+        heap
+    }
+
+    // `with_split_memory` for any block size.
+    fn from_split(pool: Memory<'heap>, colormap: Memory<'heap>) -> Option<Heap<'heap, BLOCK>> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::BLOCK_SIZE_OK;
+        let blocks = pool.len() / BLOCK;
+        if blocks == 0 || colormap.len() * BLOCKS_PER_COLORMAP_BYTE < blocks { return None }
+        if pool.overlaps(&colormap) { return None }
+        let overhead_bytes = pool.len() + colormap.len() - blocks * BLOCK;
+        let pool = pool.try_split_at(blocks * BLOCK).ok()?.0;
 
         // all of memory is free.
-        let pool = pool_data.split_at(pool_size).0;
-        Heap {
+        Some(Heap {
             start: pool.start(),
             end: pool.end(),
             blocks,
-            color_map: ColorMap::new(color_data),
+            color_map: ColorMap::new(colormap),
             free_list: FreeList::new(pool),
             current_color: Color::Blue,
             phase: Phase::QUIET,
+            zero_on_sweep: false,
+            debug_fill: None,
             check_start: ptr::null(),
             check_end: ptr::null(),
+            pointer_mask: 0,
+            mark_total_blocks: 0,
+            mark_done_blocks: 0,
+            #[cfg(feature = "profiling")]
+            marked_objects: 0,
+            #[cfg(feature = "observer")]
+            observer: None,
+            #[cfg(feature = "pinning")]
+            pins: [ptr::null(); PIN_SLOTS],
+            #[cfg(feature = "scan_limits")]
+            scan_limits: [(ptr::null(), 0); SCAN_LIMIT_SLOTS],
+            overhead_bytes,
+            live_bytes: 0,
+            #[cfg(feature = "profiling")]
+            peak_live_bytes: 0,
+            #[cfg(feature = "profiling")]
+            histogram: [0; HISTOGRAM_BUCKETS],
+            #[cfg(feature = "fuzzing")]
+            fuzz_slots: [ptr::null(); FUZZ_SLOTS],
+        })
+    }
+
+    /// Grow the heap by donating more memory to it. The new memory must
+    /// start immediately after the end of the memory the heap is currently
+    /// using (including the color map that sits at the end of it), so that
+    /// the pool stays contiguous. The color map is moved to the end of the
+    /// new memory, and the rest is added to the free list.
+    ///
+    /// Returns false (and leaves the heap unchanged) if the memory isn't
+    /// adjacent, or is too small to add any new blocks. A heap created with
+    /// [`with_split_memory`](struct.Heap.html#method.with_split_memory)
+    /// can't be extended unless its color map happens to sit right after the
+    /// pool.
+    pub fn extend(&mut self, m: Memory<'heap>) -> bool {
+        if m.start() != self.color_map.end() { return false }
+        let color_map_start = self.color_map.as_bytes().as_ptr() as usize;
+        if color_map_start < (self.end as usize) || color_map_start >= (self.end as usize) + BLOCK {
+            return false
         }
+        let whole = Memory::from_addresses(self.start, m.end());
+        let len = whole.len();
+        let (pool, color_data) = Self::layout(whole);
+        if pool.end() <= self.end { return false }
+
+        // the color map has to move before we write free-list links over it.
+        self.color_map.relocate(color_data);
+        let added = Memory::from_addresses(self.end, pool.end());
+        self.end = pool.end();
+        self.blocks = pool.len() / BLOCK;
+        self.overhead_bytes = len - pool.len();
+        self.free_list.retire(added);
+        true
     }
 
-    /// Create a new heap out of a mutable byte-slice.
-    pub fn from_bytes(bytes: &'heap mut [u8]) -> Heap<'heap> {
-        Heap::new(Memory::new(bytes))
+    /// Touch one byte in every `page` bytes of the pool, so that memory
+    /// which is committed lazily (or is slow the first time it's written)
+    /// is ready before it's needed. Each byte is read and written back
+    /// unchanged, so nothing in the heap is disturbed. Returns the number of
+    /// bytes touched.
+    pub fn prefault(&mut self, page: usize) -> usize {
+        assert!(page > 0);
+        let mut count = 0;
+        let mut p = self.start;
+        while p < self.end {
+            unsafe { ptr::write_volatile(p, ptr::read_volatile(p)) };
+            count += 1;
+            p = p.wrapping_add(page);
+        }
+        count
+    }
+
+    /// With the `fuzzing` feature: perform one step of a generated workload.
+    /// The heap remembers the allocations made by `HeapOp::Alloc` in a small
+    /// table, so that later steps can refer to them by index.
+    #[cfg(feature = "fuzzing")]
+    pub fn apply_op(&mut self, op: HeapOp) {
+        match op {
+            HeapOp::Alloc(amount) => {
+                if let Some(slot) = self.fuzz_slots.iter().position(|p| p.is_null()) {
+                    if let Some(m) = self.allocate(amount) { self.fuzz_slots[slot] = m.start() }
+                }
+            },
+            HeapOp::Retire(index) => {
+                let p = mem::replace(&mut self.fuzz_slots[index % FUZZ_SLOTS], ptr::null());
+                if !p.is_null() { self.retire_span(p) }
+            },
+            HeapOp::Gc => {
+                let roots = self.fuzz_slots;
+                self.gc_ptrs(&roots);
+            },
+            HeapOp::Coalesce => {
+                self.coalesce();
+            },
+        }
     }
 
+    /// Free every allocation at once, returning the heap to the state it was
+    /// in when it was created. Any outstanding references into the heap are
+    /// now dangling.
+    pub fn reset(&mut self) {
+        self.color_map.clear();
+        self.free_list = FreeList::new(Memory::from_addresses(self.start, self.end));
+        self.current_color = Color::Blue;
+        self.phase = Phase::QUIET;
+        self.check_start = ptr::null();
+        self.check_end = ptr::null();
+        self.mark_total_blocks = 0;
+        self.mark_done_blocks = 0;
+        #[cfg(feature = "profiling")]
+        { self.marked_objects = 0; }
+        #[cfg(feature = "pinning")]
+        { self.pins = [ptr::null(); PIN_SLOTS]; }
+        #[cfg(feature = "scan_limits")]
+        { self.scan_limits = [(ptr::null(), 0); SCAN_LIMIT_SLOTS]; }
+        self.live_bytes = 0;
+        #[cfg(feature = "profiling")]
+        { self.peak_live_bytes = 0; }
+        #[cfg(feature = "profiling")]
+        {
+            self.histogram = [0; HISTOGRAM_BUCKETS];
+            self.color_map.writes = 0;
+        }
+        #[cfg(feature = "fuzzing")]
+        { self.fuzz_slots = [ptr::null(); FUZZ_SLOTS]; }
+    }
+
+    /// The memory address of the start of a block, given its index. This
+    /// is the inverse of the block index returned by
+    /// [`allocate_indexed()`](struct.Heap.html#method.allocate_indexed).
+    /// No range check is done.
     #[inline]
-    fn address_of(&self, block: usize) -> *mut u8 {
-        ((self.start as usize) + block * BLOCK_SIZE_BYTES) as *mut u8
+    pub fn address_of(&self, block: usize) -> *mut u8 {
+        ((self.start as usize) + block * BLOCK) as *mut u8
     }
 
     #[inline]
     fn block_of(&self, p: *const u8) -> usize {
-        let mut b = ((p as usize) - (self.start as usize)) / BLOCK_SIZE_BYTES;
+        let mut b = ((p as usize) - (self.start as usize)) / BLOCK;
         while self.color_map.get(b) == Color::Continue { b -= 1 }
         b
     }
@@ -210,16 +782,74 @@ impl<'heap> Heap<'heap> {
     #[inline]
     fn block_range_of(&self, memory: &Memory, color: Color) -> BlockRange {
         let start = self.block_of(memory.start());
-        let end = start + memory.len() / BLOCK_SIZE_BYTES;
+        let end = start + memory.len() / BLOCK;
         BlockRange { start, end, color }
     }
 
+    /// Like [`address_of()`](struct.Heap.html#method.address_of), but
+    /// returns `None` if the block index is past the end of the heap.
+    pub fn address_of_block(&self, block: usize) -> Option<*mut u8> {
+        if block < self.blocks { Some(self.address_of(block)) } else { None }
+    }
+
+    /// Return the index of the first block of the span containing `p`, or
+    /// `None` if `p` isn't inside the heap's pool. For an allocated object,
+    /// this is the same index that
+    /// [`allocate_indexed()`](struct.Heap.html#method.allocate_indexed)
+    /// returned.
+    pub fn block_of_ptr(&self, p: *const u8) -> Option<usize> {
+        if p >= self.start as *const u8 && p < self.end as *const u8 { Some(self.block_of(p)) } else { None }
+    }
+
+    // a null word (like a `None` in an `Option<&T>` field) is never a
+    // pointer, even if a heap were somehow placed at address 0.
     fn is_block(&self, p: *const u8) -> bool {
-        p >= self.start && p < self.end && (p as usize) % mem::size_of::<usize>() == 0
+        !p.is_null() && p >= self.start && p < self.end && (p as usize) % mem::size_of::<usize>() == 0
     }
 
     fn get_range(&self, p: *const u8) -> BlockRange {
-        self.color_map.get_range(self.block_of(p))
+        // block_of scans backwards for the start of a span, and would run
+        // off the front of the pool if block 0 were a continuation.
+        debug_assert!(self.color_map.get(0) != Color::Continue, "heap corrupted: block 0 is marked as a continuation");
+        self.color_map.get_range(self.block_of(p), self.blocks)
+    }
+
+    // like `get_range`, for a block that's already known to be the start of
+    // a span, so there's no need to scan backwards for it.
+    #[inline]
+    fn get_range_from_start(&self, block: usize) -> BlockRange {
+        debug_assert!(self.color_map.get(block) != Color::Continue, "block {} isn't the start of a span", block);
+        self.color_map.get_range(block, self.blocks)
+    }
+
+    /// The size of each block, in bytes. Every allocation is rounded up to
+    /// a multiple of this.
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        BLOCK
+    }
+
+    /// The number of blocks in the heap's pool. `block_count() *
+    /// block_size()` is always the same as `get_stats().total_bytes`.
+    #[inline]
+    pub fn block_count(&self) -> usize {
+        self.blocks
+    }
+
+    /// The smallest amount of memory an allocation will use: one block.
+    /// Asking for fewer bytes (even zero) still costs this much.
+    #[inline]
+    pub fn min_alloc_size(&self) -> usize {
+        BLOCK
+    }
+
+    /// The largest allocation this heap could ever satisfy: the whole pool,
+    /// when nothing else is allocated. For the largest allocation that would
+    /// succeed right now, see
+    /// [`contiguous_free()`](struct.Heap.html#method.contiguous_free).
+    #[inline]
+    pub fn max_alloc_size(&self) -> usize {
+        self.blocks * BLOCK
     }
 
     /// For debugging or introspection, return the memory address of the
@@ -229,6 +859,93 @@ impl<'heap> Heap<'heap> {
         (self.start as usize, self.end as usize)
     }
 
+    /// For external tools: the raw bytes of the color map. Each byte holds
+    /// the colors of 4 blocks, 2 bits each, starting from the low bits: block
+    /// `n` is `(bytes[n / 4] >> ((n % 4) * 2)) & 3`. The values match
+    /// [`Color`](enum.Color.html): 0 = continue, 1 = blue, 2 = green, and
+    /// 3 = check. A span is a colored block followed by any "continue"
+    /// blocks. Free memory is tracked by the free list, and is always marked
+    /// as check.
+    pub fn colormap_bytes(&self) -> &[u8] {
+        self.color_map.as_bytes()
+    }
+
+    /// For external tools: the offset (in bytes) of the color map from the
+    /// start of the heap's pool.
+    pub fn colormap_offset(&self) -> usize {
+        (self.color_map.as_bytes().as_ptr() as usize) - (self.start as usize)
+    }
+
+    /// Restore a color map saved from
+    /// [`colormap_bytes()`](struct.Heap.html#method.colormap_bytes), to pick
+    /// up where a heap left off (after a reboot, for example). Along with
+    /// the contents of the pool, this rebuilds the heap's state: the free
+    /// list is rebuilt from the free spans, and the current color is taken
+    /// from the live spans. Pins and scan lengths aren't part of the color
+    /// map, so they're cleared.
+    ///
+    /// Objects never move, and pointers between them are absolute, so this
+    /// only works if the heap is at the same address it was saved from:
+    /// `base` must be the start of the saved heap's live range (from
+    /// [`get_live_range()`](struct.Heap.html#method.get_live_range)). The
+    /// snapshot must also be taken between collections, when free memory is
+    /// the only thing marked as check.
+    ///
+    /// Returns a description of the problem, without changing anything, if
+    /// a collection is in progress, `base` doesn't match, `bytes` is the
+    /// wrong length, or it doesn't describe a valid heap.
+    pub fn restore_colormap(&mut self, bytes: &[u8], base: *const u8) -> Result<(), &'static str> {
+        if self.phase != Phase::QUIET { return Err("collection in progress") }
+        if !ptr::eq(base, self.start) { return Err("heap has moved") }
+        if bytes.len() != self.color_map.as_bytes().len() { return Err("color map is the wrong size") }
+        let bits = 8 / BLOCKS_PER_COLORMAP_BYTE;
+        let color_of = |n: usize| {
+            let byte = bytes[n / BLOCKS_PER_COLORMAP_BYTE] >> ((n % BLOCKS_PER_COLORMAP_BYTE) * bits);
+            Color::try_from_bits(byte & ((1 << bits) - 1)).ok_or("bad color")
+        };
+
+        // every span must start with a color, and every live span must
+        // have the same one.
+        let mut live_color = None;
+        let mut prev = Color::Continue;
+        for n in 0..self.blocks {
+            let color = color_of(n)?;
+            match color {
+                Color::Continue => if n == 0 || prev == Color::Check { return Err("span has no start") },
+                Color::Blue | Color::Green => {
+                    if live_color.is_some_and(|c| c != color) { return Err("live spans have different colors") }
+                    live_color = Some(color);
+                },
+                Color::Check => (),
+            }
+            prev = color;
+        }
+
+        self.color_map.copy_from(bytes);
+        self.free_list = FreeList::empty();
+        let mut n = 0;
+        while n < self.blocks {
+            let range = self.color_map.get_range(n, self.blocks);
+            if range.color == Color::Check {
+                let mut end = n + 1;
+                while end < self.blocks && self.color_map.get(end) == Color::Check { end += 1 }
+                self.free_list.retire(Memory::from_addresses(self.address_of(n), self.address_of(end)));
+                n = end;
+            } else {
+                n = range.end;
+            }
+        }
+        if let Some(color) = live_color { self.current_color = color }
+        self.live_bytes = self.blocks * BLOCK - self.free_list.bytes();
+        #[cfg(feature = "profiling")]
+        { self.peak_live_bytes = cmp::max(self.peak_live_bytes, self.live_bytes); }
+        #[cfg(feature = "pinning")]
+        { self.pins = [ptr::null(); PIN_SLOTS]; }
+        #[cfg(feature = "scan_limits")]
+        { self.scan_limits = [(ptr::null(), 0); SCAN_LIMIT_SLOTS]; }
+        Ok(())
+    }
+
     /// If this pointer refers to an object allocated from within this heap,
     /// return a u32 offset which can be used to identify it on 64-bit
     /// systems where the heap will never exceed 4GB.
@@ -248,6 +965,66 @@ impl<'heap> Heap<'heap> {
         self.safe_ref_mut(((self.start as usize) + (offset as usize)) as *mut T)
     }
 
+    /// If this object was allocated from this heap, return a compact handle
+    /// for it, which can be turned back into a reference with
+    /// [`resolve`](struct.Heap.html#method.resolve). Returns `None` for
+    /// anything that isn't the start of a live allocation.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn handle_of<T>(&self, obj: &T) -> Option<HeapRef> {
+        let p = obj as *const T as *const u8;
+        if !self.is_ptr_inside(p) { return None }
+        let offset = (p as usize) - (self.start as usize);
+        let block = offset / BLOCK;
+        if offset % BLOCK != 0 || !self.is_allocation_start(block) { return None }
+        Some(HeapRef(block as u32))
+    }
+
+    /// Turn a handle from [`handle_of`](struct.Heap.html#method.handle_of)
+    /// back into a reference, if it still refers to a live allocation.
+    pub fn resolve<T>(&self, h: HeapRef) -> Option<&'heap T> {
+        let block = h.0 as usize;
+        if !self.is_allocation_start(block) { return None }
+        self.safe_ref(self.address_of(block) as *const T)
+    }
+
+    /// Is `obj` the start of a live allocation in this heap? Unlike
+    /// [`is_ptr_inside()`](struct.Heap.html#method.is_ptr_inside), this is
+    /// false for a pointer into the middle of an allocation or into free
+    /// memory.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn contains<T>(&self, obj: &T) -> bool {
+        let p = obj as *const T as *const u8;
+        if !self.is_ptr_inside(p) { return false }
+        let offset = (p as usize) - (self.start as usize);
+        offset % BLOCK == 0 && self.is_allocation_start(offset / BLOCK)
+    }
+
+    /// Find the live allocation containing `p`, if any, and return its base
+    /// address, size in bytes, and current color. Returns `None` if `p` is
+    /// outside the heap or in free memory.
+    ///
+    /// This is meant for fault handlers that want to report which object a
+    /// bad address belongs to, so it doesn't allocate, and won't panic even
+    /// if the heap's metadata has been damaged.
+    pub fn lookup(&self, p: *const u8) -> Option<(*const u8, usize, Color)> {
+        if p < self.start as *const u8 || p >= self.end as *const u8 { return None }
+        if self.free_list.iter().any(|b| b.start() as *const u8 <= p && p < b.end() as *const u8) { return None }
+        let mut block = ((p as usize) - (self.start as usize)) / BLOCK;
+        while block > 0 && self.color_map.get(block) == Color::Continue { block -= 1 }
+        let range = self.color_map.get_range(block, self.blocks);
+        if range.color == Color::Continue { return None }
+        let end = cmp::min(range.end, self.blocks);
+        Some((self.address_of(range.start) as *const u8, (end - range.start) * BLOCK, range.color))
+    }
+
+    // is this block the start of an allocation? the color map isn't
+    // authoritative for free memory, so also rule out the free list.
+    fn is_allocation_start(&self, block: usize) -> bool {
+        if block >= self.blocks || self.color_map.get(block) == Color::Continue { return false }
+        let p = self.address_of(block);
+        !self.free_list.iter().any(|b| b.start() <= p && p < b.end())
+    }
+
     /// If this pointer refers to an object allocated from within this heap,
     /// turn it into a real reference.
     pub fn safe_ref<T>(&self, ptr: *const T) -> Option<&'heap T> {
@@ -261,6 +1038,11 @@ impl<'heap> Heap<'heap> {
     }
 
     /// Was this object allocated from within this heap?
+    ///
+    /// This is only a range check: it's also true for pointers into the
+    /// middle of an object, or into free memory. Use
+    /// [`contains()`](struct.Heap.html#method.contains) to check for a live
+    /// allocation.
     pub fn is_ptr_inside<T>(&self, ptr: *const T) -> bool {
         let start = ptr as usize;
         let end = start + mem::size_of::<T>();
@@ -268,23 +1050,89 @@ impl<'heap> Heap<'heap> {
     }
 
     /// Request a `amount` bytes of memory. The size will be rounded up to
-    /// a multiple of the block size. Returns `None` if a block of memory
-    /// that big isn't available,
+    /// a multiple of the block size, so even a request for 0 bytes will
+    /// allocate one block. Returns `None` if a block of memory that big
+    /// isn't available,
     pub fn allocate(&mut self, amount: usize) -> Option<Memory<'heap>> {
-        self.free_list.allocate(ceil_to(amount, BLOCK_SIZE_BYTES)).map(|mut m| {
-            let color = if self.phase == Phase::MARKING { Color::Check } else { self.current_color };
-            self.color_map.set_range(self.block_range_of(&m, color));
-            if self.phase == Phase::MARKING {
-                self.add_to_check_span(m.start());
-            }
-            m.clear();
-            m
+        self.free_list.allocate(self.actual_size(amount)).map(|m| self.claim(m))
+    }
+
+    /// The number of bytes that an allocation of `amount` bytes will really
+    /// use, after rounding up to a multiple of the block size.
+    pub fn actual_size(&self, amount: usize) -> usize {
+        ceil_to(cmp::max(amount, 1), BLOCK)
+    }
+
+    /// Like [`allocate()`](struct.Heap.html#method.allocate), but also
+    /// return the number of extra bytes at the end of the memory that were
+    /// only added to round it up to a whole block. They belong to the
+    /// allocation, so they can be used for anything.
+    pub fn allocate_exact(&mut self, amount: usize) -> Option<(Memory<'heap>, usize)> {
+        self.allocate(amount).map(|m| {
+            let padding = m.len() - amount;
+            (m, padding)
         })
     }
 
+    /// Like [`allocate()`](struct.Heap.html#method.allocate), but also
+    /// return the index of the first block of the allocation. Objects never
+    /// move, so the index is stable for as long as the memory is alive, and
+    /// can be used as a key into side tables.
+    pub fn allocate_indexed(&mut self, amount: usize) -> Option<(Memory<'heap>, usize)> {
+        self.allocate(amount).map(|m| {
+            let block = ((m.start() as usize) - (self.start as usize)) / BLOCK;
+            (m, block)
+        })
+    }
+
+    /// Like [`allocate()`](struct.Heap.html#method.allocate), but the memory
+    /// will start at an address that's a multiple of `align`, which must be
+    /// a power of 2. Every allocation is already aligned to the block size
+    /// if the heap's memory is, so this is only needed for bigger
+    /// alignments, or a heap in memory with an unknown alignment.
+    pub fn allocate_aligned(&mut self, amount: usize, align: usize) -> Option<Memory<'heap>> {
+        assert!(align.is_power_of_two());
+        let amount = self.actual_size(amount);
+        self.free_list.allocate_aligned(amount, align, BLOCK).map(|m| self.claim(m))
+    }
+
+    /// Like [`allocate()`](struct.Heap.html#method.allocate), but take the
+    /// memory from the high end of the heap instead of the low end. Keeping
+    /// long-lived objects at the top of the heap and short-lived objects at
+    /// the bottom stops them from interleaving, so freeing the short-lived
+    /// objects leaves bigger free spans behind.
+    pub fn allocate_high(&mut self, amount: usize) -> Option<Memory<'heap>> {
+        self.free_list.allocate_high(self.actual_size(amount)).map(|m| self.claim(m))
+    }
+
+    // color & account for memory that was just taken off the free list.
+    fn claim(&mut self, mut m: Memory<'heap>) -> Memory<'heap> {
+        let color = if self.phase == Phase::MARKING { Color::Check } else { self.current_color };
+        self.color_map.set_range(self.block_range_of(&m, color));
+        if self.phase == Phase::MARKING {
+            self.add_to_check_span(m.start());
+        }
+        self.live_bytes += m.len();
+        #[cfg(feature = "profiling")]
+        { self.peak_live_bytes = cmp::max(self.peak_live_bytes, self.live_bytes); }
+        #[cfg(feature = "profiling")]
+        {
+            let bucket = cmp::min(m.len() / BLOCK, HISTOGRAM_BUCKETS) - 1;
+            self.histogram[bucket] += 1;
+        }
+        match self.debug_fill {
+            Some((fill, _)) => m.fill(fill),
+            None => m.clear(),
+        }
+        m
+    }
+
     /// Request enough memory to hold an object of type `T`. The object will
     /// be initialized to its default value. Returns `None` if a block of
     /// memory that big isn't available.
+    ///
+    /// Zero-sized types don't consume any heap memory: they get a dangling
+    /// (but aligned and non-null) reference, the same way `Box` handles them.
     pub fn allocate_object<T: Default>(&mut self) -> Option<&'heap mut T> {
         self.allocate_dynamic_object(0)
     }
@@ -293,28 +1141,218 @@ impl<'heap> Heap<'heap> {
     /// dynamic-sized padding. The object will be initialized to its default
     /// value. Returns `None` if a block of memory that big isn't available.
     pub fn allocate_dynamic_object<T: Default>(&mut self, padding: usize) -> Option<&'heap mut T> {
-        self.allocate(mem::size_of::<T>() + padding).map(|m| {
-            let obj: &'heap mut T = unsafe { mem::transmute(m.inner().as_mut_ptr()) };
-            *obj = T::default();
-            obj
+        if mem::size_of::<T>() + padding == 0 {
+            // zero-sized types need no storage, just like `Box<ZST>`.
+            let obj = ptr::NonNull::<T>::dangling().as_ptr();
+            unsafe {
+                ptr::write(obj, T::default());
+                return Some(&mut *obj);
+            }
+        }
+        // the memory may be filled with junk (or a debug pattern), so write
+        // the new object without dropping whatever "was" there.
+        self.allocate(mem::size_of::<T>() + padding).map(|m| unsafe {
+            let obj = m.inner().as_mut_ptr() as *mut T;
+            ptr::write(obj, T::default());
+            &mut *obj
         })
     }
 
+    /// Allocate an object (like
+    /// [`allocate_object()`](struct.Heap.html#method.allocate_object)) and
+    /// pin it, so that it's treated as a root by every collection until it's
+    /// unpinned. This is meant for a few objects that live as long as the
+    /// program does, like a global symbol table. Only 4 objects can be
+    /// pinned at once; if the pin set is full, this returns `None`.
+    /// Zero-sized objects aren't in the heap, so they're never pinned.
+    #[cfg(feature = "pinning")]
+    pub fn allocate_pinned<T: Default>(&mut self) -> Option<&'heap mut T> {
+        if mem::size_of::<T>() == 0 { return self.allocate_object::<T>() }
+        let slot = self.pins.iter().position(|p| p.is_null())?;
+        let obj = self.allocate_object::<T>()?;
+        self.pins[slot] = obj as *const T as *const u8;
+        Some(obj)
+    }
+
+    /// Like [`allocate()`](struct.Heap.html#method.allocate), but only the
+    /// first `scan_len` bytes of the allocation will be scanned for pointers
+    /// during marking. The rest can hold data (like a byte buffer) that might
+    /// look like pointers, without keeping other objects alive by accident.
+    ///
+    /// Only 4 objects can have a scan length at once, and the slot is freed
+    /// when the object is. If they're all in use, this returns `None`
+    /// without allocating, the same as if the heap were full.
+    #[cfg(feature = "scan_limits")]
+    pub fn allocate_with_scan_len(&mut self, amount: usize, scan_len: usize) -> Option<Memory<'heap>> {
+        let slot = self.scan_limits.iter().position(|(p, _)| p.is_null())?;
+        let m = self.allocate(amount)?;
+        self.scan_limits[slot] = (m.start(), scan_len);
+        Some(m)
+    }
+
+    // how many bytes of the span starting at `p` should be scanned for pointers?
+    // always whole words, so a partial word at the end is scanned in full.
+    #[cfg(feature = "scan_limits")]
+    fn scan_len_of(&self, p: *const u8, len: usize) -> usize {
+        let word = mem::size_of::<usize>();
+        self.scan_limits.iter().find(|(start, _)| *start == p).map(|(_, n)| cmp::min(div_ceil(*n, word) * word, len)).unwrap_or(len)
+    }
+
+    #[cfg(not(feature = "scan_limits"))]
+    #[inline]
+    fn scan_len_of(&self, _p: *const u8, len: usize) -> usize {
+        len
+    }
+
+    // drop the pin or scan length of any object that's been freed. while
+    // marking, live objects can be check too, so pins are left alone: a
+    // dropped scan length is harmless, but a dropped pin is not.
+    fn forget_freed(&mut self) {
+        #[cfg(feature = "pinning")]
+        if self.phase != Phase::MARKING {
+            for i in 0..PIN_SLOTS {
+                let p = self.pins[i];
+                if self.is_ptr_inside(p) && self.color_map.get(self.block_of(p)) == Color::Check {
+                    self.pins[i] = ptr::null();
+                }
+            }
+        }
+        #[cfg(feature = "scan_limits")]
+        for i in 0..SCAN_LIMIT_SLOTS {
+            let p = self.scan_limits[i].0;
+            if !p.is_null() && self.color_map.get(self.block_of(p)) == Color::Check {
+                self.scan_limits[i] = (ptr::null(), 0);
+            }
+        }
+    }
+
+    /// Allocate an object and register it as a persistent root in one step,
+    /// so there's no moment where it exists but isn't rooted. The heap has
+    /// no separate root registry: persistent roots are the pin set, so this
+    /// is the same as
+    /// [`allocate_pinned()`](struct.Heap.html#method.allocate_pinned), and
+    /// shares its limit of 4 objects. Returns `None` if the pin set is full
+    /// or the memory isn't available. Use
+    /// [`unpin()`](struct.Heap.html#method.unpin) to drop the root.
+    #[cfg(feature = "pinning")]
+    pub fn allocate_root<T: Default>(&mut self) -> Option<&'heap mut T> {
+        self.allocate_pinned()
+    }
+
+    /// Stop treating a pinned object as a root. It will be freed by the next
+    /// collection if nothing else refers to it. Returns false if it wasn't
+    /// pinned.
+    #[cfg(feature = "pinning")]
+    pub fn unpin<T>(&mut self, obj: &T) -> bool {
+        let p = obj as *const T as *const u8;
+        if !self.is_ptr_inside(p) { return false }
+        self.pins.iter_mut().find(|pin| **pin == p).map(|pin| *pin = ptr::null()).is_some()
+    }
+
     /// Request enough memory to hold an array of `count` objects of type `T`.
     /// Each object in the array will be initialized to its default value.
     /// Returns `None` if a block of memory that big isn't available.
+    ///
+    /// The array is only aligned to the block size. For a type with a bigger
+    /// alignment, use
+    /// [`allocate_array_aligned()`](struct.Heap.html#method.allocate_array_aligned).
     pub fn allocate_array<T: Default>(&mut self, count: usize) -> Option<&'heap mut [T]> {
-        self.allocate(mem::size_of::<T>() * count).map(|m| unsafe {
-            let array: &'heap mut [T] = slice::from_raw_parts_mut(mem::transmute(m.inner().as_mut_ptr()), count);
-            for item in array.iter_mut() {
-                *item = T::default();
+        debug_assert!(mem::align_of::<T>() <= BLOCK, "array type is aligned past the block size");
+        self.allocate(mem::size_of::<T>() * count).map(|m| Self::init_array(m, count))
+    }
+
+    /// Like [`allocate_array()`](struct.Heap.html#method.allocate_array), but
+    /// the array will start at an address that's a multiple of `T`'s
+    /// alignment, even if it's bigger than the block size.
+    pub fn allocate_array_aligned<T: Default>(&mut self, count: usize) -> Option<&'heap mut [T]> {
+        let align = cmp::max(mem::align_of::<T>(), BLOCK);
+        self.allocate_aligned(mem::size_of::<T>() * count, align).map(|m| Self::init_array(m, count))
+    }
+
+    fn init_array<T: Default>(m: Memory<'heap>, count: usize) -> &'heap mut [T] {
+        unsafe {
+            let p = m.inner().as_mut_ptr() as *mut T;
+            for i in 0..count {
+                ptr::write(p.add(i), T::default());
             }
-            array
+            slice::from_raw_parts_mut(p, count)
+        }
+    }
+
+    /// Request enough memory to hold an object of type `T` followed by an
+    /// array of `count` items of type `U`, like a C struct with a flexible
+    /// array member. The array starts at the first properly aligned address
+    /// after `T`. The object and each item are initialized to their default
+    /// values. Returns `None` if a block of memory that big isn't available,
+    /// or if `T` or `U` needs more alignment than the block size, since the
+    /// allocation itself is only aligned to a block.
+    pub fn allocate_with_tail<T: Default, U: Default>(&mut self, count: usize) -> Option<(&'heap mut T, &'heap mut [U])> {
+        if cmp::max(mem::align_of::<T>(), mem::align_of::<U>()) > BLOCK { return None }
+        let offset = ceil_to(mem::size_of::<T>(), mem::align_of::<U>());
+        self.allocate(offset + mem::size_of::<U>() * count).map(|m| unsafe {
+            let p = m.inner().as_mut_ptr();
+            ptr::write(p as *mut T, T::default());
+            let tail = p.add(offset) as *mut U;
+            for i in 0..count {
+                ptr::write(tail.add(i), U::default());
+            }
+            (&mut *(p as *mut T), slice::from_raw_parts_mut(tail, count))
+        })
+    }
+
+    /// Allocate an object (like
+    /// [`allocate_object()`](struct.Heap.html#method.allocate_object)) for
+    /// temporary use, returning a guard that retires it when it's dropped.
+    /// The guard borrows the heap, so the object must be finished with
+    /// before the heap can be used again. Returns `None` if a block of
+    /// memory that big isn't available.
+    pub fn alloc_scoped<T: Default + 'heap>(&mut self) -> Option<ScopedObj<'_, 'heap, T, BLOCK>> {
+        let obj = self.allocate_object::<T>()? as *mut T;
+        Some(ScopedObj { heap: self, obj })
+    }
+
+    /// Reserve `total` bytes (rounded up to a whole block) as one contiguous
+    /// region, so that small objects allocated from it will be next to each
+    /// other in memory. Returns `None` if a block of memory that big isn't
+    /// available. The heap can't be used until the region is dropped.
+    pub fn bump_region(&mut self, total: usize) -> Option<BumpRegion<'_, 'heap, BLOCK>> {
+        self.free_list.allocate(self.actual_size(total)).map(move |m| {
+            let (next, end) = (m.start(), m.end());
+            BumpRegion { heap: self, next, end }
+        })
+    }
+
+    /// Request enough memory to hold a copy of a string, and copy it in.
+    /// Returns `None` if a block of memory that big isn't available.
+    pub fn allocate_str(&mut self, s: &str) -> Option<&'heap str> {
+        self.allocate(s.len()).map(|m| {
+            let bytes = &mut m.inner()[0 .. s.len()];
+            bytes.copy_from_slice(s.as_bytes());
+            unsafe { str::from_utf8_unchecked(bytes) }
+        })
+    }
+
+    /// Copy a string into a [`GcStr`](struct.GcStr.html), which holds the
+    /// length and bytes together in one allocation, so it can be used as a
+    /// root. Returns `None` if a block of memory that big isn't available.
+    pub fn allocate_gc_str(&mut self, s: &str) -> Option<&'heap mut GcStr> {
+        self.allocate(mem::size_of::<GcStr>() + s.len()).map(|m| unsafe {
+            // both pointers come from the whole allocation, not the header.
+            let base = m.inner().as_mut_ptr();
+            let data = base.add(mem::size_of::<GcStr>());
+            ptr::copy_nonoverlapping(s.as_ptr(), data, s.len());
+            let obj = base as *mut GcStr;
+            ptr::write(obj, GcStr::new(s.len(), data));
+            &mut *obj
         })
     }
 
     /// Given an object that was allocated on this heap, how many bytes were
     /// allocated to it?
+    ///
+    /// This reports the size of the whole allocation containing `obj`, so
+    /// passing an element from the middle of an array will report the size
+    /// of the entire array (rounded up to a block).
     pub fn size_of<T>(&self, obj: &T) -> usize {
         self.size_of_ptr(obj as *const T)
     }
@@ -322,23 +1360,102 @@ impl<'heap> Heap<'heap> {
     /// Given a pointer to an object that was allocated on this heap, how
     /// many bytes are allocated to it?
     pub fn size_of_ptr<T>(&self, obj: *const T) -> usize {
-        if !self.is_ptr_inside(obj) { return 0 }
-        let range = self.get_range(obj as *const u8);
+        self.allocation_size(obj as *const u8)
+    }
+
+    /// How many bytes are in the allocation containing `p`? `p` may point
+    /// anywhere inside the allocation. Returns 0 if `p` isn't in this heap.
+    pub fn allocation_size(&self, p: *const u8) -> usize {
+        if !self.is_ptr_inside(p) { return 0 }
+        let range = self.get_range(p);
         (self.address_of(range.end) as usize) - (self.address_of(range.start) as usize)
     }
 
     /// Give back an allocation without waiting for a GC round.
+    ///
+    /// Panics if the memory isn't inside this heap, is already free, or the
+    /// free list is damaged.
     pub fn retire(&mut self, m: Memory<'heap>) {
-        self.color_map.free_range(self.block_range_of(&m, Color::Check));
-        self.free_list.retire(m);
+        match self.try_retire(m) {
+            Ok(()) => (),
+            Err(GcError::ForeignMemory) => panic!("retired memory isn't inside this heap"),
+            Err(GcError::AlreadyFree) => panic!("retired memory is already free"),
+            Err(e) => panic!("retire failed: {:?}", e),
+        }
+    }
+
+    /// Like [`retire()`](struct.Heap.html#method.retire), but returns an
+    /// error instead of panicking if the memory isn't inside this heap, is
+    /// already free, or the free list is damaged. Either way, the heap is
+    /// left unchanged.
+    pub fn try_retire(&mut self, m: Memory<'heap>) -> Result<(), GcError> {
+        if !self.is_ptr_inside(m.start()) || (m.end() as usize) > (self.end as usize) {
+            return Err(GcError::ForeignMemory);
+        }
+        let range = self.block_range_of(&m, Color::Check);
+        let (start, end, len) = (m.start(), m.end(), m.len());
+        if let Err(m) = self.free_list.try_retire(m) {
+            return Err(if self.is_already_free(&m) { GcError::AlreadyFree } else { GcError::CorruptFreeList });
+        }
+        self.fill_freed(start, end);
+        self.color_map.free_range(range);
+        self.live_bytes -= len;
+        self.forget_freed();
+        Ok(())
     }
 
     /// Give back an allocated object without waiting for a GC round.
+    /// Zero-sized objects were never in the heap, so they're ignored.
+    ///
+    /// Panics if the object isn't inside this heap, is already free, or
+    /// isn't the start of an allocation (for example, a reference to one
+    /// field of an object), so that a stray reference can't free the object
+    /// around it.
     pub fn retire_object<T>(&mut self, obj: &'heap mut T) {
-        let range = self.get_range(obj as *mut T as *const T as *const u8);
-        let m = Memory::from_addresses(self.address_of(range.start), self.address_of(range.end));
+        if mem::size_of::<T>() == 0 { return; }
+        self.retire_span(obj as *mut T as *const T as *const u8);
+    }
+
+    /// Give back an array from
+    /// [`allocate_array()`](struct.Heap.html#method.allocate_array) without
+    /// waiting for a GC round. The whole allocation is freed, including any
+    /// space it was rounded up to fill.
+    ///
+    /// Panics if the array isn't inside this heap, is already free, or
+    /// doesn't start at the beginning of an allocation.
+    pub fn retire_array<T>(&mut self, array: &'heap mut [T]) {
+        self.retire_span(array.as_ptr() as *const u8);
+    }
+
+    // does any part of `m` overlap memory that's already on the free list?
+    // the color map can't tell: while marking, live objects are check too.
+    // this walks the whole list, so it's only used to explain a failed retire.
+    fn is_already_free(&self, m: &Memory) -> bool {
+        self.free_list.iter().any(|block| block.start() < m.end() && m.start() < block.end())
+    }
+
+    // free the whole span that `p` points into.
+    pub(crate) fn retire_span(&mut self, p: *const u8) {
+        assert!(self.is_ptr_inside(p), "retired memory isn't inside this heap");
+        let range = self.get_range(p);
+        assert!(ptr::eq(self.address_of(range.start), p), "retired object isn't the start of an allocation");
+        let (start, end) = (self.address_of(range.start), self.address_of(range.end));
+        let len = (end as usize) - (start as usize);
+        if let Err(m) = self.free_list.try_retire(Memory::from_addresses(start, end)) {
+            assert!(!self.is_already_free(&m), "retired memory is already free");
+            panic!("free list is damaged");
+        }
+        self.fill_freed(start, end);
         self.color_map.free_range(range);
-        self.free_list.retire(m);
+        self.live_bytes -= len;
+        #[cfg(feature = "pinning")]
+        for pin in self.pins.iter_mut() {
+            if *pin == p { *pin = ptr::null() }
+        }
+        #[cfg(feature = "scan_limits")]
+        for limit in self.scan_limits.iter_mut() {
+            if limit.0 == p { *limit = (ptr::null(), 0) }
+        }
     }
 
     /// Start the first phase of garbage collection. This is only useful if
@@ -357,12 +1474,83 @@ impl<'heap> Heap<'heap> {
     /// you modified by calling
     /// [`mark_check`](struct.Heap.html#method.mark_check).
     pub fn mark_start<T>(&mut self, roots: &[&T]) {
-        assert!(self.phase == Phase::QUIET);
+        self.try_mark_start(roots).expect("mark_start called during a collection");
+    }
+
+    /// Like [`mark_start()`](struct.Heap.html#method.mark_start), but
+    /// returns an error instead of panicking if a collection is already in
+    /// progress.
+    pub fn try_mark_start<T>(&mut self, roots: &[&T]) -> Result<(), GcError> {
+        self.begin_marking()?;
+        for r in roots { self.check(*r as *const T as *const u8) }
+        Ok(())
+    }
+
+    /// Like [`mark_start()`](struct.Heap.html#method.mark_start), but the
+    /// roots are raw pointers, so they can point to objects of different
+    /// types. The scan is conservative, so only the address matters.
+    pub fn mark_start_ptrs(&mut self, roots: &[*const u8]) {
+        self.begin_mark();
+        for r in roots { self.check(*r) }
+    }
+
+    /// Start the mark phase like
+    /// [`mark_start()`](struct.Heap.html#method.mark_start), but without any
+    /// roots yet. If your roots come from several places, add each batch
+    /// with [`add_mark_roots()`](struct.Heap.html#method.add_mark_roots) or
+    /// [`mark_slice()`](struct.Heap.html#method.mark_slice) before calling
+    /// [`mark_round()`](struct.Heap.html#method.mark_round).
+    pub fn begin_mark(&mut self) {
+        self.begin_marking().expect("mark_start called during a collection");
+    }
+
+    /// Add more roots during the mark phase, after
+    /// [`begin_mark()`](struct.Heap.html#method.begin_mark) or
+    /// [`mark_start()`](struct.Heap.html#method.mark_start).
+    pub fn add_mark_roots<T>(&mut self, roots: &[&T]) {
+        assert!(self.phase == Phase::MARKING);
+        for r in roots { self.check(*r as *const T as *const u8) }
+    }
+
+    // flip the live color and mark the pinned objects: everything in
+    // `mark_start` except the roots.
+    fn begin_marking(&mut self) -> Result<(), GcError> {
+        if self.phase != Phase::QUIET { return Err(GcError::WrongPhase) }
         self.check_start = ptr::null();
         self.check_end = ptr::null();
         self.current_color = self.current_color.opposite();
-        for r in roots { self.check(*r as *const T as *const u8) }
+        self.mark_total_blocks = self.live_bytes / BLOCK;
+        self.mark_done_blocks = 0;
+        #[cfg(feature = "profiling")]
+        { self.marked_objects = 0; }
+        #[cfg(feature = "observer")]
+        if let Some(mut observer) = self.observer { unsafe { observer.as_mut() }.on_mark_start() }
+        #[cfg(feature = "pinning")]
+        for i in 0..PIN_SLOTS { self.check(self.pins[i]) }
         self.phase = Phase::MARKING;
+        Ok(())
+    }
+
+    /// Like [`mark_start()`](struct.Heap.html#method.mark_start), but return
+    /// the number of roots that don't point into this heap. `mark_start`
+    /// quietly ignores those, which is what a conservative collector
+    /// wants, but while testing it's usually a bug (a stack reference
+    /// passed by mistake, for example) that would let a live object be
+    /// collected.
+    pub fn mark_start_checked<T>(&mut self, roots: &[&T]) -> usize {
+        let bad = roots.iter().filter(|r| self.as_candidate(**r as *const T as *const u8).is_none()).count();
+        self.mark_start(roots);
+        bad
+    }
+
+    /// Add a slice of words as extra roots, after
+    /// [`mark_start()`](struct.Heap.html#method.mark_start). Each word is
+    /// treated as a possible pointer, the same way the words inside objects
+    /// are, so this is a natural way to pass in registers or a stack that
+    /// have been spilled into memory.
+    pub fn mark_slice(&mut self, roots: &[usize]) {
+        assert!(self.phase == Phase::MARKING);
+        for r in roots { self.check(*r as *const u8) }
     }
 
     /// Do one "round" of the mark phase of garbage collection. This is only
@@ -381,13 +1569,26 @@ impl<'heap> Heap<'heap> {
     /// you modified by calling
     /// [`mark_check`](struct.Heap.html#method.mark_check).
     pub fn mark_round(&mut self) -> bool {
-        assert!(self.phase == Phase::MARKING);
+        match self.try_mark_round() {
+            Ok(done) => done,
+            Err(GcError::WrongPhase) => panic!("mark_round called outside the mark phase"),
+            Err(e) => panic!("mark_round failed: {:?}", e),
+        }
+    }
+
+    /// Like [`mark_round()`](struct.Heap.html#method.mark_round), but
+    /// returns an error instead of panicking if the heap isn't in the mark
+    /// phase, or the range of memory waiting to be checked is damaged.
+    pub fn try_mark_round(&mut self) -> Result<bool, GcError> {
+        if self.phase != Phase::MARKING { return Err(GcError::WrongPhase) }
         if self.check_start == ptr::null() {
             self.phase = Phase::MARKED;
-            return true;
+            return Ok(true);
         }
 
+        // add_to_check_span only ever widens the range, so it can't be inverted.
         let (start, end) = (self.check_start, self.check_end);
+        if end.is_null() || start > end { return Err(GcError::CorruptCheckSpan) }
         self.check_start = ptr::null();
         self.check_end = ptr::null();
 
@@ -398,8 +1599,10 @@ impl<'heap> Heap<'heap> {
             let end_addr = self.address_of(r.end) as *const usize;
             if r.color == Color::Check {
                 // pretend the whole memory block is words, and traverse it, marking anything we find.
+                let scan_len = self.scan_len_of(start_addr as *const u8, (r.end - r.start) * BLOCK);
+                let scan_end = ((start_addr as usize) + scan_len) as *const usize;
                 let mut p = start_addr;
-                while p < end_addr {
+                while p < scan_end {
                     let word = unsafe { *p } as *const u8;
                     self.check(word);
                     p = ((p as usize) + mem::size_of::<usize>()) as *const usize;
@@ -412,9 +1615,9 @@ impl<'heap> Heap<'heap> {
         // we're done marking if there's no new span to check.
         if self.check_start == ptr::null() {
             self.phase = Phase::MARKED;
-            true
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 
@@ -440,22 +1643,67 @@ impl<'heap> Heap<'heap> {
         let p = obj as *const T as *const u8;
         if self.is_block(p) {
             let block = self.block_of(p);
+            if self.color_map.get(block) == self.current_color.opposite() { self.count_reached(block) }
             self.color_map.set(block, Color::Check);
             self.add_to_check_span(p);
         }
     }
 
-    fn check(&mut self, p: *const u8) {
-        if self.is_block(p) {
-            let block = self.block_of(p);
+    /// Mark an array to be re-checked because some of its elements have
+    /// been modified during the mark phase of garbage collection. The array
+    /// is a single span, so this is the same as calling
+    /// [`mark_check`](struct.Heap.html#method.mark_check) on each element,
+    /// but only does the work once.
+    pub fn mark_check_slice<T>(&mut self, array: &[T]) {
+        let p = array.as_ptr() as *const u8;
+        if array.is_empty() || p < self.start as *const u8 || p >= self.end as *const u8 { return }
+        let block = self.block_of(p);
+        if self.color_map.get(block) == self.current_color.opposite() { self.count_reached(block) }
+        self.color_map.set(block, Color::Check);
+        self.add_to_check_span(p);
+        self.add_to_check_span(&array[array.len() - 1] as *const T as *const u8);
+    }
+
+    /// Return a [`Marker`](struct.Marker.html) for marking objects from a
+    /// [`Trace`](trait.Trace.html) implementation. This is only useful
+    /// during the mark phase, after
+    /// [`mark_start()`](struct.Heap.html#method.mark_start) and before
+    /// [`mark_round()`](struct.Heap.html#method.mark_round) returns `true`.
+    pub fn marker(&mut self) -> Marker<'_, 'heap, BLOCK> {
+        assert!(self.phase == Phase::MARKING);
+        Marker::new(self)
+    }
+
+    // if this word looks like a pointer into the heap, return the pointer
+    // (without any tag bits) and the first block of the span it points into.
+    fn as_candidate(&self, word: *const u8) -> Option<(*const u8, usize)> {
+        let p = ((word as usize) & !self.pointer_mask) as *const u8;
+        if self.is_block(p) { Some((p, self.block_of(p))) } else { None }
+    }
+
+    pub(crate) fn check(&mut self, p: *const u8) {
+        if let Some((p, block)) = self.as_candidate(p) {
             if self.color_map.get(block) == self.current_color.opposite() {
+                self.count_reached(block);
                 self.color_map.set(block, Color::Check);
                 self.add_to_check_span(p);
             }
         }
     }
 
+    // count an object the first time it's reached in a mark phase, as it
+    // goes from the old color to check. one that's checked again later
+    // (by `mark_check`) was already counted.
+    fn count_reached(&mut self, block: usize) {
+        let r = self.color_map.get_range(block, self.blocks);
+        self.mark_done_blocks += r.end - r.start;
+        #[cfg(feature = "profiling")]
+        { self.marked_objects += 1; }
+    }
+
     fn add_to_check_span(&mut self, p: *const u8) {
+        // mark_round walks this range, so it must never leave the pool.
+        let p = cmp::min(cmp::max(p, self.start as *const u8), (self.end as *const u8).wrapping_sub(1));
         if self.check_start == ptr::null() || self.check_start > p {
             self.check_start = p;
         }
@@ -464,21 +1712,229 @@ impl<'heap> Heap<'heap> {
         }
     }
 
+    /// Set the bits that should be cleared from each word before checking
+    /// if it's a pointer into the heap. If your runtime stores a type tag
+    /// in the low bits of its pointers, this lets the conservative scanner
+    /// still recognize them. The default is 0 (no tag bits).
+    pub fn set_pointer_mask(&mut self, mask: usize) {
+        self.pointer_mask = mask;
+    }
+
+    /// If set, [`sweep()`](struct.Heap.html#method.sweep) zeroes each span
+    /// of garbage before returning it to the free list, so that old data
+    /// (like keys or passwords) doesn't linger in free memory. The first few
+    /// bytes of each free span are still used to link the free list. This
+    /// makes sweeping slower. The default is off.
+    pub fn set_zero_on_sweep(&mut self, zero: bool) {
+        self.zero_on_sweep = zero;
+    }
+
+    /// For debugging: fill new allocations with one byte and freed memory
+    /// with another (as `Some((alloc_fill, free_fill))`) instead of zero,
+    /// so that reads of uninitialized or freed memory stand out in a dump.
+    /// Freed memory is filled by [`sweep()`](struct.Heap.html#method.sweep)
+    /// and the `retire` calls, except for the first few bytes of each free
+    /// span, which link the free list. The free fill replaces
+    /// [`set_zero_on_sweep()`](struct.Heap.html#method.set_zero_on_sweep).
+    ///
+    /// The conservative scanner will treat the fill as data like any other,
+    /// so pick bytes that can't form a pointer into the heap, like `0xcd`
+    /// and `0xdd`. The default is `None` (allocations are zeroed).
+    pub fn set_debug_fill(&mut self, fill: Option<(u8, u8)>) {
+        self.debug_fill = fill;
+    }
+
+    // scribble over memory that's just been freed, if asked to. it's already
+    // on the free list, so leave alone the start, where a header may be.
+    fn fill_freed(&self, start: *mut u8, end: *mut u8) {
+        if let Some((_, fill)) = self.debug_fill {
+            if (end as usize) - (start as usize) > FREE_HEADER_SIZE {
+                Memory::from_addresses(((start as usize) + FREE_HEADER_SIZE) as *mut u8, end).fill(fill);
+            }
+        }
+    }
+
+    /// Attach a [`GcObserver`](trait.GcObserver.html) to be notified as the
+    /// garbage collector marks and sweeps, or `None` to detach it.
+    #[cfg(feature = "observer")]
+    pub fn set_observer(&mut self, observer: Option<&'heap mut (dyn GcObserver + 'heap)>) {
+        self.observer = observer.map(ptr::NonNull::from);
+    }
+
     /// For debugging and tests, report the current range of addresses that
     /// will be scanned on the next `mark_round`.
     pub fn get_mark_range(&self) -> (*const u8, *const u8) {
         (self.check_start, self.check_end)
     }
 
+    /// For progress reports: return how many blocks have been reached by
+    /// marking so far in this collection (each object is counted once, even
+    /// if it's checked again), and an estimate of how many there are in
+    /// total (the number of blocks allocated when marking started). The
+    /// first number never goes down, and never passes the second. Once
+    /// marking is done, they're equal.
+    pub fn mark_progress(&self) -> (usize, usize) {
+        if self.phase == Phase::MARKING {
+            (self.mark_done_blocks, self.mark_total_blocks)
+        } else {
+            (self.mark_total_blocks, self.mark_total_blocks)
+        }
+    }
+
+    /// The color of live objects in this cycle. It flips between `Blue`
+    /// and `Green` at each
+    /// [`mark_start()`](struct.Heap.html#method.mark_start): objects that
+    /// are reached by marking are given the new color, and anything left
+    /// with the old color at the end is garbage. This is the color to look
+    /// for in a dump to find live objects.
+    pub fn current_color(&self) -> Color {
+        self.current_color
+    }
+
     /// Sweep through the heap and move every un-marked span of memory into
     /// the free list. This is the 2nd and final phase of garbage collection.
-    pub fn sweep(&mut self) {
-        assert!(self.phase == Phase::MARKED);
-        self.iter().filter(|span| span.span_type == SpanType::Color(self.current_color.opposite())).for_each(|span| {
-            let m = Memory::from_addresses(span.start, span.end);
-            span.free_list_span.insert(m);
-        });
-        self.phase = Phase::QUIET;
+    ///
+    /// Returns the number of bytes that were freed.
+    pub fn sweep(&mut self) -> usize {
+        match self.try_sweep() {
+            Ok(reclaimed) => reclaimed,
+            Err(GcError::WrongPhase) => panic!("sweep called before marking finished"),
+            Err(e) => panic!("sweep failed: {:?}", e),
+        }
+    }
+
+    /// Like [`sweep()`](struct.Heap.html#method.sweep), but returns an error
+    /// instead of panicking if marking isn't finished, or if a dead span
+    /// couldn't be put back on the free list because the free list is
+    /// damaged. In that case, the sweep stops early.
+    pub fn try_sweep(&mut self) -> Result<usize, GcError> {
+        if self.phase != Phase::MARKED { return Err(GcError::WrongPhase) }
+        let rv = self.free_color_spans(self.current_color.opposite());
+        if let Ok(_reclaimed) = rv {
+            #[cfg(feature = "observer")]
+            if let Some(mut observer) = self.observer { unsafe { observer.as_mut() }.on_gc_end(_reclaimed) }
+            self.phase = Phase::QUIET;
+        }
+        rv
+    }
+
+    /// Free every object of the given color right away, without marking.
+    /// This is a sweep aimed at an explicit color: for example, after
+    /// switching to a fresh set of objects, everything from before can be
+    /// dropped at once with `free_color(old_color)`. The caller is promising
+    /// that none of those objects are reachable anymore, and any references
+    /// to them are now dangling.
+    ///
+    /// Live objects are always `Blue` or `Green`, so any other color frees
+    /// nothing. Returns the number of bytes freed, or `GcError::WrongPhase`
+    /// if a collection is in progress.
+    pub fn free_color(&mut self, color: Color) -> Result<usize, GcError> {
+        if self.phase != Phase::QUIET { return Err(GcError::WrongPhase) }
+        if color != Color::Blue && color != Color::Green { return Ok(0) }
+        self.free_color_spans(color)
+    }
+
+    // move every span of this color to the free list, returning the number
+    // of bytes freed.
+    fn free_color_spans(&mut self, color: Color) -> Result<usize, GcError> {
+        let mut reclaimed = 0;
+        let dead = SpanKind::Live(color);
+        let zero = self.zero_on_sweep;
+        let fill = self.debug_fill;
+        let mut rv = Ok(());
+
+        // walk the heap like HeapIterator does, but without borrowing it, so
+        // each reclaimed span can be marked as check as soon as it's freed.
+        let mut free_list_span = self.free_list.iter_span().next().unwrap();
+        let mut block = 0;
+        while block < self.blocks {
+            let current = self.address_of(block);
+            if let Some(free) = free_list_span.ptr.ptr {
+                if free.start() <= current {
+                    free_list_span = free_list_span.next().unwrap();
+                    if free.start() == current { block = (free.end() as usize - self.start as usize) / BLOCK }
+                    continue;
+                }
+            }
+
+            let span = self.get_range_from_start(block);
+            block = span.end;
+            if SpanKind::Live(span.color) != dead { continue }
+            let mut m = Memory::from_addresses(current, self.address_of(span.end));
+            let len = m.len();
+            #[cfg(feature = "observer")]
+            if let Some(mut observer) = self.observer { unsafe { observer.as_mut() }.on_sweep_span(m.start(), len) }
+            match fill {
+                Some((_, fill)) => m.fill(fill),
+                None => if zero { m.clear() },
+            }
+            if free_list_span.insert(m).is_err() {
+                rv = Err(GcError::CorruptFreeList);
+                break;
+            }
+            self.color_map.free_range(BlockRange { start: span.start, end: span.end, color: Color::Check });
+            reclaimed += len;
+        }
+        if reclaimed > 0 { self.forget_freed() }
+        self.live_bytes -= reclaimed;
+        rv.map(|_| reclaimed)
+    }
+
+    /// Estimate how many bytes a GC would free right now, without changing
+    /// anything in the heap. This follows the same pointers a real mark
+    /// phase would, so it returns exactly what a
+    /// [`gc()`](struct.Heap.html#method.gc) with the same `roots` would
+    /// reclaim.
+    ///
+    /// Since it can't use the color map, it needs a scratch buffer to
+    /// track which objects it's reached, of at least
+    /// [`garbage_estimate_scratch_len()`](struct.Heap.html#method.garbage_estimate_scratch_len)
+    /// bytes. If the buffer is too small, it returns
+    /// `GcError::ScratchTooSmall`, and if a collection is in progress, it
+    /// returns `GcError::WrongPhase`.
+    pub fn estimate_garbage<T>(&self, roots: &[&T], scratch: &mut [u8]) -> Result<usize, GcError> {
+        if self.phase != Phase::QUIET { return Err(GcError::WrongPhase) }
+        if scratch.len() < self.garbage_estimate_scratch_len() { return Err(GcError::ScratchTooSmall) }
+        for b in scratch.iter_mut() { *b = 0 }
+
+        // bit 0 of each pair: reached. bit 1: scanned.
+        let has = |scratch: &[u8], block: usize, bit: usize| scratch[block / 4] & (1 << ((block % 4) * 2 + bit)) != 0;
+        let set = |scratch: &mut [u8], block: usize, bit: usize| scratch[block / 4] |= 1 << ((block % 4) * 2 + bit);
+        let reach = |scratch: &mut [u8], word: *const u8| {
+            if let Some((_, block)) = self.as_candidate(word) {
+                if self.is_allocation_start(block) { set(scratch, block, 0) }
+            }
+        };
+
+        for r in roots { reach(scratch, *r as *const T as *const u8) }
+        #[cfg(feature = "pinning")]
+        for pin in self.pins.iter() { reach(scratch, *pin) }
+        let mut scanning = true;
+        while scanning {
+            scanning = false;
+            for span in self.spans() {
+                let block = ((span.start as usize) - (self.start as usize)) / BLOCK;
+                if span.kind == SpanKind::Free || !has(scratch, block, 0) || has(scratch, block, 1) { continue }
+                set(scratch, block, 1);
+                scanning = true;
+                let scan_len = self.scan_len_of(span.start, span.len);
+                let words = unsafe { slice::from_raw_parts(span.start as *const usize, scan_len / mem::size_of::<usize>()) };
+                for word in words { reach(scratch, *word as *const u8) }
+            }
+        }
+
+        Ok(self.spans().filter(|span| {
+            let block = ((span.start as usize) - (self.start as usize)) / BLOCK;
+            span.kind != SpanKind::Free && !has(scratch, block, 0)
+        }).map(|span| span.len).sum())
+    }
+
+    /// How big a scratch buffer
+    /// [`estimate_garbage()`](struct.Heap.html#method.estimate_garbage)
+    /// needs: 2 bits per block, so one byte for every 4 blocks in the heap.
+    #[inline]
+    pub fn garbage_estimate_scratch_len(&self) -> usize {
+        div_ceil(self.blocks, BLOCKS_PER_COLORMAP_BYTE)
     }
 
     /// Do an entire GC round, freeing any currently unused memory.
@@ -493,15 +1949,68 @@ impl<'heap> Heap<'heap> {
         self.sweep();
     }
 
-    fn iter(&self) -> HeapIterator {
+    /// Do an entire GC round with no roots except the pinned objects (see
+    /// [`allocate_root()`](struct.Heap.html#method.allocate_root) and
+    /// [`allocate_pinned()`](struct.Heap.html#method.allocate_pinned)).
+    /// Without the `pinning` feature, there are no roots at all, so every
+    /// object is freed.
+    pub fn gc_roots(&mut self) {
+        self.gc_ptrs(&[]);
+    }
+
+    /// Like [`gc()`](struct.Heap.html#method.gc), but the roots are raw
+    /// pointers, so they can point to objects of different types.
+    pub fn gc_ptrs(&mut self, roots: &[*const u8]) {
+        self.mark_start_ptrs(roots);
+        while !self.mark_round() {}
+        self.sweep();
+    }
+
+    /// Like [`gc()`](struct.Heap.html#method.gc), but returns an error
+    /// instead of panicking if a collection is already in progress, or the
+    /// free list is damaged.
+    pub fn try_gc<T>(&mut self, roots: &[&T]) -> Result<(), GcError> {
+        self.try_mark_start(roots)?;
+        while !self.try_mark_round()? {}
+        self.try_sweep().map(|_| ())
+    }
+
+    /// Make sure that a following `allocate(amount)` will succeed, running a
+    /// full [`gc()`](struct.Heap.html#method.gc) first if there isn't a big
+    /// enough free span. Nothing is allocated. Returns false if there still
+    /// isn't enough contiguous space after collecting.
+    pub fn reserve<T>(&mut self, amount: usize, roots: &[&T]) -> bool {
+        let needed = self.actual_size(amount);
+        if self.contiguous_free() >= needed { return true }
+        self.gc(roots);
+        self.coalesce();
+        self.contiguous_free() >= needed
+    }
+
+    fn iter(&self) -> HeapIterator<'_, BLOCK> {
         HeapIterator::new(self)
     }
 
+    /// Walk the heap in address order, yielding each free or allocated
+    /// [`Span`](struct.Span.html) of memory.
+    pub fn spans(&self) -> Spans<'_, BLOCK> {
+        Spans(self.iter())
+    }
+
+    /// Walk the free list in address order, yielding the start address and
+    /// size (in bytes) of each free span. This is the same information as
+    /// the free spans from [`spans()`](struct.Heap.html#method.spans),
+    /// without walking the allocated memory too.
+    pub fn free_spans(&self) -> impl Iterator<Item = (*const u8, usize)> + '_ {
+        self.free_list.iter().map(|block| (block.start() as *const u8, block.size))
+    }
+
     /// For debugging: generate a string listing the size and color of each
-    /// span of memory.
-    pub fn dump<W: fmt::Write>(&self, buffer: &mut W) -> fmt::Result {
+    /// span of memory. It can be written to any `fmt::Write` (like a UART
+    /// driver), including a `&mut dyn fmt::Write`.
+    pub fn dump<W: fmt::Write + ?Sized>(&self, buffer: &mut W) -> fmt::Result {
         let mut first = true;
-        for span in self.iter() {
+        for span in self.spans() {
             if !first { write!(buffer, ", ")?; }
             first = false;
             write!(buffer, "{:?}", span)?;
@@ -509,43 +2018,311 @@ impl<'heap> Heap<'heap> {
         Ok(())
     }
 
-    pub fn dump_into<'a>(&self, bytes: &'a mut [u8]) -> &'a str {
+    /// Generate the [`dump()`](struct.Heap.html#method.dump) string into a
+    /// byte buffer. Returns an error if the buffer was too small to hold the
+    /// whole dump.
+    pub fn dump_into<'a>(&self, bytes: &'a mut [u8]) -> Result<&'a str, fmt::Error> {
         let mut b = StringBuffer::new(bytes);
-        self.dump(&mut b).ok();
-        b.to_str()
+        self.dump(&mut b)?;
+        Ok(b.to_str())
     }
 
     /// For debugging: generate a string listing _only_ the color of each
     /// span of memory.
-    pub fn dump_spans<W: fmt::Write>(&self, buffer: &mut W) {
+    pub fn dump_spans<W: fmt::Write + ?Sized>(&self, buffer: &mut W) -> fmt::Result {
+        let mut first = true;
+        for span in self.spans() {
+            if !first { write!(buffer, ", ")?; }
+            first = false;
+            write!(buffer, "{:?}", span.kind)?;
+        }
+        Ok(())
+    }
+
+    /// Generate the [`dump_spans()`](struct.Heap.html#method.dump_spans)
+    /// string into a byte buffer. Returns an error if the buffer was too
+    /// small to hold the whole dump.
+    pub fn dump_spans_into<'a>(&self, bytes: &'a mut [u8]) -> Result<&'a str, fmt::Error> {
+        let mut b = StringBuffer::new(bytes);
+        self.dump_spans(&mut b)?;
+        Ok(b.to_str())
+    }
+
+    /// For debugging the color map: generate a string listing the color,
+    /// first block, and number of blocks of each span, like
+    /// `Green@0:2, FREE@2:13`.
+    pub fn dump_blocks<W: fmt::Write + ?Sized>(&self, buffer: &mut W) -> fmt::Result {
         let mut first = true;
-        for span in self.iter() {
-            if !first && write!(buffer, ", ").is_err() { return; }
+        for span in self.spans() {
+            if !first { write!(buffer, ", ")?; }
             first = false;
-            if write!(buffer, "{:?}", span.span_type).is_err() { return; }
+            let block = ((span.start as usize) - (self.start as usize)) / BLOCK;
+            write!(buffer, "{:?}@{}:{}", span.kind, block, span.len / BLOCK)?;
         }
+        Ok(())
     }
 
-    pub fn dump_spans_into<'a>(&self, bytes: &'a mut [u8]) -> &'a str {
+    /// Generate the [`dump_blocks()`](struct.Heap.html#method.dump_blocks)
+    /// string into a byte buffer. Returns an error if the buffer was too
+    /// small to hold the whole dump.
+    pub fn dump_blocks_into<'a>(&self, bytes: &'a mut [u8]) -> Result<&'a str, fmt::Error> {
         let mut b = StringBuffer::new(bytes);
-        self.dump_spans(&mut b);
+        self.dump_blocks(&mut b)?;
+        Ok(b.to_str())
+    }
+
+    /// For status displays: write a one-line summary of how full the heap
+    /// is, like `mwgc: 1234/4096 bytes (30%), 3 free spans, largest 2048`.
+    pub fn summary<W: fmt::Write + ?Sized>(&self, buffer: &mut W) -> fmt::Result {
+        write!(
+            buffer, "mwgc: {}/{} bytes ({}%), {} free spans, largest {}",
+            self.live_bytes, self.blocks * BLOCK, self.utilization_permille() / 10,
+            self.free_list.iter().count(), self.free_list.largest()
+        )
+    }
+
+    /// Generate the [`summary()`](struct.Heap.html#method.summary) string
+    /// into a byte buffer. If the buffer is too small, the summary is
+    /// truncated.
+    pub fn summary_into<'a>(&self, bytes: &'a mut [u8]) -> &'a str {
+        let mut b = StringBuffer::new_truncating(bytes);
+        let _ = self.summary(&mut b);
         b.to_str()
     }
 
+    /// For crash dumps: write out the contents of every live span, in a
+    /// simple line-based text format. The first line describes the heap:
+    ///
+    /// ```text
+    /// mwgc snapshot start=<address> bytes=<pool size> block=<block size>
+    /// ```
+    ///
+    /// followed by one line per live span, with its offset from the start of
+    /// the heap (in bytes), its length (in bytes), its color, and its
+    /// contents as hex:
+    ///
+    /// ```text
+    /// <offset> <length> <color> <hex bytes>
+    /// ```
+    ///
+    /// Objects never move, so any word that falls within
+    /// `start .. start + bytes` can be treated as a (conservative) pointer to
+    /// another span.
+    pub fn snapshot<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
+        let size = (self.end as usize) - (self.start as usize);
+        writeln!(w, "mwgc snapshot start={:#x} bytes={} block={}", self.start as usize, size, BLOCK)?;
+        for span in self.spans() {
+            if let SpanKind::Live(color) = span.kind {
+                let offset = (span.start as usize) - (self.start as usize);
+                write!(w, "{} {} {:?} ", offset, span.len, color)?;
+                let data = unsafe { slice::from_raw_parts(span.start, span.len) };
+                for byte in data { write!(w, "{:02x}", byte)?; }
+                writeln!(w)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// For debugging: write the object graph as a graphviz "dot" file. Each
+    /// live span is a node, labeled with its offset from the start of the
+    /// heap and its size. Each word inside a span that looks like a pointer
+    /// to another live span (using the same test as the garbage collector)
+    /// becomes an edge. The `roots` are linked from a separate "roots" node.
+    pub fn to_dot<T, W: fmt::Write + ?Sized>(&self, roots: &[&T], w: &mut W) -> fmt::Result {
+        writeln!(w, "digraph mwgc {{")?;
+        writeln!(w, "  roots [shape=box];")?;
+        for r in roots {
+            if let Some(target) = self.dot_target(*r as *const T as *const u8) {
+                writeln!(w, "  roots -> n{};", target)?;
+            }
+        }
+        for span in self.spans() {
+            if let SpanKind::Live(_) = span.kind {
+                let offset = (span.start as usize) - (self.start as usize);
+                writeln!(w, "  n{} [label=\"{} ({})\"];", offset, offset, span.len)?;
+                let words = unsafe { slice::from_raw_parts(span.start as *const usize, span.len / mem::size_of::<usize>()) };
+                for word in words {
+                    if let Some(target) = self.dot_target(*word as *const u8) {
+                        writeln!(w, "  n{} -> n{};", offset, target)?;
+                    }
+                }
+            }
+        }
+        writeln!(w, "}}")
+    }
+
+    // offset of the live span this word points into, if any.
+    fn dot_target(&self, word: *const u8) -> Option<usize> {
+        self.as_candidate(word).and_then(|(_, block)| {
+            if self.is_allocation_start(block) { Some(block * BLOCK) } else { None }
+        })
+    }
+
+    /// Return the size of the largest allocation that would currently
+    /// succeed: the biggest contiguous span of free memory. Unlike
+    /// `get_stats().free_bytes`, this doesn't count memory that's free but
+    /// fragmented into smaller spans.
+    pub fn contiguous_free(&self) -> usize {
+        self.free_list.largest()
+    }
+
+    /// Return the address and size (in bytes) of the biggest live span, or
+    /// `None` if nothing is allocated. This is the counterpart to
+    /// [`contiguous_free()`](struct.Heap.html#method.contiguous_free), for
+    /// finding out if one giant object is using up the heap.
+    pub fn largest_live_object(&self) -> Option<(*const u8, usize)> {
+        self.spans()
+            .filter(|span| span.kind != SpanKind::Free)
+            .fold(None, |best: Option<Span>, span| match best {
+                Some(b) if b.len >= span.len => Some(b),
+                _ => Some(span),
+            })
+            .map(|span| (span.start, span.len))
+    }
+
+    /// Walk the live objects whose allocation is exactly `bytes` long (after
+    /// rounding up to whole blocks), yielding the address of each. This is
+    /// for leak hunting: if there are far more 48-byte objects than you
+    /// expect, this will find them.
+    pub fn live_of_size(&self, bytes: usize) -> impl Iterator<Item = *const u8> + '_ {
+        self.spans().filter(move |span| span.kind != SpanKind::Free && span.len == bytes).map(|span| span.start)
+    }
+
+    /// Return the number of free bytes that could actually be handed out by
+    /// allocations, if you were to allocate until the heap was full.
+    /// `get_stats().free_bytes` counts every byte on the free list, but
+    /// allocations are made in whole blocks, so any part of a free span
+    /// smaller than a block can't be used. (Free spans in a heap are always
+    /// whole blocks, so today these agree, but the free list doesn't promise
+    /// it.)
+    pub fn allocatable_bytes(&self) -> usize {
+        self.free_list.usable_bytes(BLOCK)
+    }
+
+    /// Walk the free list and merge any adjacent free spans into one,
+    /// returning the number of merges done. Spans are normally merged as
+    /// they're freed, so this is only useful as a defragmentation pass.
+    pub fn coalesce(&mut self) -> usize {
+        self.free_list.coalesce()
+    }
+
+    /// Check the heap's internal invariants, returning a description of the
+    /// first problem found. This is meant for debugging: if something has
+    /// scribbled over the heap's metadata, this should catch it before a
+    /// later operation wanders off into memory it doesn't own.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.blocks > 0 && self.color_map.get(0) == Color::Continue {
+            return Err("block 0 is marked as a continuation");
+        }
+        let mut last_end = self.start as *const u8;
+        for block in self.free_list.iter() {
+            let (start, end) = (block.start() as *const u8, block.end() as *const u8);
+            if start < self.start as *const u8 || end > self.end as *const u8 {
+                return Err("free block is outside the heap");
+            }
+            if start < last_end { return Err("free list is out of order or overlapping") }
+            if block.size == 0 || block.size % BLOCK != 0 {
+                return Err("free block has an invalid size");
+            }
+            last_end = end;
+        }
+        if self.free_list.bytes() != self.free_bytes() { return Err("free byte count is out of sync with the free list") }
+        Ok(())
+    }
+
+    /// The fraction of the heap that's allocated, from 0.0 (empty) to 1.0
+    /// (full).
+    pub fn utilization(&self) -> f32 {
+        let total = self.blocks * BLOCK;
+        if total == 0 { 0.0 } else { self.live_bytes as f32 / total as f32 }
+    }
+
+    /// Like [`utilization()`](struct.Heap.html#method.utilization), but in
+    /// parts per thousand, for targets where floating point is expensive.
+    pub fn utilization_permille(&self) -> u32 {
+        let total = self.blocks * BLOCK;
+        if total == 0 { 0 } else { ((self.live_bytes as u64) * 1000 / (total as u64)) as u32 }
+    }
+
+    /// For debugging: check the tri-color invariant, that no live object
+    /// (one that's been marked and scanned) holds a pointer to an object
+    /// that hasn't been reached yet. If this fails at the end of marking,
+    /// something changed a live object without calling
+    /// [`mark_check()`](struct.Heap.html#method.mark_check), and the object
+    /// it points to will be freed while it's still in use. On failure,
+    /// returns the address of the word holding the bad pointer.
+    ///
+    /// This scans every live object, so it's meant for tests and debug
+    /// builds, not for every collection.
+    pub fn check_tricolor_invariant(&self) -> Result<(), *const u8> {
+        let (live, dead) = (SpanKind::Live(self.current_color), self.current_color.opposite());
+        for span in self.spans().filter(|span| span.kind == live) {
+            let start = span.start as *const usize;
+            let end = ((span.start as usize) + span.len) as *const usize;
+            let mut p = start;
+            while p < end {
+                let word = unsafe { *p } as *const u8;
+                if let Some((_, block)) = self.as_candidate(word) {
+                    if self.color_map.get(block) == dead { return Err(p as *const u8) }
+                }
+                p = ((p as usize) + mem::size_of::<usize>()) as *const usize;
+            }
+        }
+        Ok(())
+    }
+
+    /// With the `profiling` feature: the number of allocations made so far,
+    /// by size. Index 0 counts 1-block allocations, index 1 counts 2-block
+    /// allocations, and so on, with the last entry counting every
+    /// allocation of 5 blocks or more.
+    #[cfg(feature = "profiling")]
+    pub fn alloc_histogram(&self) -> &[usize] {
+        &self.histogram
+    }
+
+    // every byte is either allocated or on the free list, so there's no
+    // need to walk the free list to count them.
+    #[inline]
+    fn free_bytes(&self) -> usize {
+        self.blocks * BLOCK - self.live_bytes
+    }
+
     /// Return an object listing the free & total bytes of this heap.
     pub fn get_stats(&self) -> HeapStats {
         HeapStats {
-            total_bytes: self.blocks * BLOCK_SIZE_BYTES,
-            free_bytes: self.free_list.bytes(),
+            total_bytes: self.blocks * BLOCK,
+            free_bytes: self.free_bytes(),
+            overhead_bytes: self.overhead_bytes,
+            #[cfg(feature = "profiling")]
+            peak_live_bytes: self.peak_live_bytes,
+            #[cfg(feature = "profiling")]
+            marked_objects: self.marked_objects,
+            #[cfg(feature = "profiling")]
+            colormap_writes: self.color_map.writes,
             start: self.start,
             end: self.end,
         }
     }
 }
 
-impl<'a> fmt::Debug for Heap<'a> {
+impl<'a, 'heap, const BLOCK: usize> IntoIterator for &'a Heap<'heap, BLOCK> {
+    type Item = Span;
+    type IntoIter = Spans<'a, BLOCK>;
+
+    fn into_iter(self) -> Spans<'a, BLOCK> {
+        self.spans()
+    }
+}
+
+impl<'a, const BLOCK: usize> fmt::Display for Heap<'a, BLOCK> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.dump_spans(f)
+    }
+}
+
+impl<'a, const BLOCK: usize> fmt::Debug for Heap<'a, BLOCK> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Heap(pool={:?}, blocks={}x{}, ", self.start, self.blocks, BLOCK_SIZE_BYTES)?;
+        write!(f, "Heap(pool={:?}, blocks={}x{}, ", self.start, self.blocks, BLOCK)?;
         if f.alternate() {
             self.dump(f)?;
         } else {
@@ -22,39 +22,79 @@
 //! ```
 
 #![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 
 #[macro_use]
 extern crate static_assertions;
 
+#[cfg(feature = "allocator_api")]
+mod allocator;
 mod color_map;
 mod free_list;
+mod gc_str;
+mod gc_vec;
 mod heap;
+#[cfg(feature = "critical-section")]
+mod locked_heap;
 mod memory;
+#[cfg(feature = "observer")]
+mod observer;
+#[cfg(target_has_atomic = "8")]
+mod static_heap;
 mod string_buffer;
+mod trace;
 
-pub use self::heap::{Heap, HeapStats};
+#[cfg(feature = "allocator_api")]
+pub use self::allocator::HeapAllocator;
+pub use self::color_map::Color;
+pub use self::gc_str::GcStr;
+pub use self::gc_vec::GcVec;
+pub use self::heap::{BumpRegion, GcError, Heap, HeapInitError, HeapRef, HeapStats, ScopedObj, Span, SpanKind, Spans};
+#[cfg(feature = "fuzzing")]
+pub use self::heap::HeapOp;
+#[cfg(feature = "critical-section")]
+pub use self::locked_heap::LockedHeap;
 pub use self::memory::Memory;
+#[cfg(feature = "observer")]
+pub use self::observer::GcObserver;
+#[cfg(target_has_atomic = "8")]
+pub use self::static_heap::StaticHeap;
 pub use self::string_buffer::StringBuffer;
+pub use self::trace::{Marker, Trace};
 
 /// how many bytes are in each block of memory?
 /// smaller means more overhead wasted for tracking memory. larger means more wasted memory.
 const BLOCK_SIZE_BYTES: usize = 16;
 
+/// The smallest block size a [`Heap`](struct.Heap.html) can use. Each free
+/// block holds a free-list link and a size, so this is 2 words: 4 bytes on
+/// a 16-bit system, 8 bytes on a 32-bit system, and 16 on a 64-bit system.
+pub const MIN_BLOCK_SIZE: usize = free_list::FREE_BLOCK_SIZE;
+
 // block size must be big enough to hold linking info for the free list.
-const_assert!(block_size; BLOCK_SIZE_BYTES >= free_list::FREE_BLOCK_SIZE);
+const_assert!(block_size; BLOCK_SIZE_BYTES >= MIN_BLOCK_SIZE);
+
+// the free-block header follows the pointer width, so 16-bit targets (like
+// msp430) can use blocks as small as 4 bytes.
+#[cfg(target_pointer_width = "16")]
+const_assert!(min_block_size; MIN_BLOCK_SIZE == 4);
+#[cfg(target_pointer_width = "32")]
+const_assert!(min_block_size; MIN_BLOCK_SIZE == 8);
+#[cfg(target_pointer_width = "64")]
+const_assert!(min_block_size; MIN_BLOCK_SIZE == 16);
 
 
 // odd that this isn't in the stdlib, but apparently neither is divmod!
-fn div_ceil(numerator: usize, denominator: usize) -> usize {
+const fn div_ceil(numerator: usize, denominator: usize) -> usize {
     let floor = numerator / denominator;
     let rem = numerator % denominator;
     if rem == 0 { floor } else { floor + 1 }
 }
 
-fn floor_to(n: usize, chunk: usize) -> usize {
+const fn floor_to(n: usize, chunk: usize) -> usize {
     n / chunk * chunk
 }
 
-fn ceil_to(n: usize, chunk: usize) -> usize {
+const fn ceil_to(n: usize, chunk: usize) -> usize {
     div_ceil(n, chunk) * chunk
 }
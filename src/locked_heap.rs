@@ -0,0 +1,69 @@
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+use crate::heap::{Heap, HeapStats};
+use crate::memory::Memory;
+
+/// A [`Heap`](struct.Heap.html) that can be shared between an interrupt
+/// handler and the main program. Each call takes a critical section (using
+/// the `critical-section` crate) around the heap operation.
+///
+/// This only makes each call atomic: a `gc` will run entirely inside a
+/// critical section, so interrupts are blocked for the whole collection.
+/// If that's too long, it's up to you to use the incremental GC calls
+/// through [`lock`](#method.lock) instead.
+pub struct LockedHeap<'heap> {
+    heap: Mutex<RefCell<Heap<'heap>>>,
+}
+
+// every access to the heap goes through a critical section.
+unsafe impl<'heap> Sync for LockedHeap<'heap> {}
+
+impl<'heap> LockedHeap<'heap> {
+    /// Wrap a heap.
+    pub fn new(heap: Heap<'heap>) -> LockedHeap<'heap> {
+        LockedHeap { heap: Mutex::new(RefCell::new(heap)) }
+    }
+
+    /// Run a function with exclusive access to the heap, inside a critical
+    /// section.
+    pub fn lock<R, F: FnOnce(&mut Heap<'heap>) -> R>(&self, f: F) -> R {
+        critical_section::with(|cs| f(&mut self.heap.borrow_ref_mut(cs)))
+    }
+
+    /// Equivalent to [`Heap::allocate`](struct.Heap.html#method.allocate).
+    pub fn allocate(&self, amount: usize) -> Option<Memory<'heap>> {
+        self.lock(|h| h.allocate(amount))
+    }
+
+    /// Equivalent to [`Heap::allocate_object`](struct.Heap.html#method.allocate_object).
+    pub fn allocate_object<T: Default>(&self) -> Option<&'heap mut T> {
+        self.lock(|h| h.allocate_object())
+    }
+
+    /// Equivalent to [`Heap::allocate_array`](struct.Heap.html#method.allocate_array).
+    pub fn allocate_array<T: Default>(&self, count: usize) -> Option<&'heap mut [T]> {
+        self.lock(|h| h.allocate_array(count))
+    }
+
+    /// Equivalent to [`Heap::retire`](struct.Heap.html#method.retire).
+    pub fn retire(&self, m: Memory<'heap>) {
+        self.lock(|h| h.retire(m))
+    }
+
+    /// Equivalent to [`Heap::retire_object`](struct.Heap.html#method.retire_object).
+    pub fn retire_object<T>(&self, obj: &'heap mut T) {
+        self.lock(move |h| h.retire_object(obj))
+    }
+
+    /// Equivalent to [`Heap::gc`](struct.Heap.html#method.gc). The entire
+    /// collection runs inside one critical section.
+    pub fn gc<T>(&self, roots: &[&T]) {
+        self.lock(|h| h.gc(roots))
+    }
+
+    /// Equivalent to [`Heap::get_stats`](struct.Heap.html#method.get_stats).
+    pub fn get_stats(&self) -> HeapStats {
+        self.lock(|h| h.get_stats())
+    }
+}
@@ -22,9 +22,28 @@ impl<'heap> Memory<'heap> {
         (Memory(m1), Memory(m2))
     }
 
+    /// Like [`split_at`](struct.Memory.html#method.split_at), but if `n` is
+    /// past the end, return the memory unchanged instead of panicking.
+    pub fn try_split_at(self, n: usize) -> Result<(Memory<'heap>, Memory<'heap>), Memory<'heap>> {
+        if n > self.0.len() { return Err(self) }
+        Ok(self.split_at(n))
+    }
+
+    /// Do these two spans of memory share any bytes? Two `Memory`s made
+    /// from slices can't, but ones made with
+    /// [`from_addresses`](struct.Memory.html#method.from_addresses) might.
+    pub fn overlaps(&self, other: &Memory) -> bool {
+        self.start() < other.end() && other.start() < self.end()
+    }
+
     /// Zero out this memory.
     pub fn clear(&mut self) {
-        for i in 0..(self.0.len()) { self.0[i] = 0 }
+        self.fill(0);
+    }
+
+    /// Set every byte of this memory to `byte`.
+    pub fn fill(&mut self, byte: u8) {
+        for i in 0..(self.0.len()) { self.0[i] = byte }
     }
 
     /// Convert back into a mutable slice of memory, consuming this object.
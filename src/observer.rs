@@ -0,0 +1,15 @@
+/// Callbacks for watching the garbage collector work, for profiling or
+/// tuning. Attach one to a heap with
+/// [`Heap::set_observer`](struct.Heap.html#method.set_observer). Each method
+/// does nothing by default, so you only need to implement the ones you
+/// care about.
+pub trait GcObserver {
+    /// The mark phase has started.
+    fn on_mark_start(&mut self) {}
+
+    /// The sweep phase freed a span of `len` bytes at `start`.
+    fn on_sweep_span(&mut self, _start: *const u8, _len: usize) {}
+
+    /// The sweep phase is over, and freed `reclaimed` bytes in total.
+    fn on_gc_end(&mut self, _reclaimed: usize) {}
+}
@@ -0,0 +1,123 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::BLOCK_SIZE_BYTES;
+use crate::heap::{Heap, HeapStats};
+use crate::memory::Memory;
+
+// keep the buffer aligned to the block size, so objects are too.
+#[repr(align(16))]
+struct Buffer<const N: usize>([u8; N]);
+
+const_assert!(static_heap_align; BLOCK_SIZE_BYTES <= 16);
+
+/// A heap in a static buffer of `N` bytes, for the common case of one heap
+/// for the whole program. Declare one with
+/// [`static_heap!`](macro.static_heap.html), and the [`Heap`](struct.Heap.html)
+/// will be created the first time it's used.
+///
+/// **Important**: This is meant for single-threaded programs. Only one
+/// caller can use the heap at a time: calling back into the heap from inside
+/// [`with`](#method.with), or from another thread or an interrupt handler
+/// while it's in use, will panic instead of waiting. (To share a heap with
+/// an interrupt handler, see `LockedHeap`.) It needs an atomic swap, so it
+/// isn't available on targets without one.
+pub struct StaticHeap<const N: usize> {
+    data: UnsafeCell<Buffer<N>>,
+    heap: UnsafeCell<Option<Heap<'static>>>,
+    busy: AtomicBool,
+}
+
+// every access to the heap goes through `with`, which won't hand out a
+// second reference while the first is in use.
+unsafe impl<const N: usize> Sync for StaticHeap<N> {}
+
+// clears the busy flag when `with` is done, even if it panicked.
+struct Release<'a>(&'a AtomicBool);
+
+impl<'a> Drop for Release<'a> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl<const N: usize> StaticHeap<N> {
+    pub const fn new() -> StaticHeap<N> {
+        StaticHeap { data: UnsafeCell::new(Buffer([0; N])), heap: UnsafeCell::new(None), busy: AtomicBool::new(false) }
+    }
+
+    /// Run a function with access to the heap, creating it first if this
+    /// is the first use.
+    ///
+    /// Panics if the heap is already in use.
+    pub fn with<R, F: FnOnce(&mut Heap<'static>) -> R>(&'static self, f: F) -> R {
+        assert!(!self.busy.swap(true, Ordering::Acquire), "static heap is already in use");
+        let _release = Release(&self.busy);
+        let heap = unsafe { &mut *self.heap.get() };
+        if heap.is_none() {
+            let data = unsafe { &mut (*self.data.get()).0 };
+            *heap = Some(Heap::new(Memory::new(data)));
+        }
+        f(heap.as_mut().unwrap())
+    }
+
+    /// Equivalent to [`Heap::allocate`](struct.Heap.html#method.allocate).
+    pub fn allocate(&'static self, amount: usize) -> Option<Memory<'static>> {
+        self.with(|h| h.allocate(amount))
+    }
+
+    /// Equivalent to [`Heap::allocate_object`](struct.Heap.html#method.allocate_object).
+    pub fn allocate_object<T: Default>(&'static self) -> Option<&'static mut T> {
+        self.with(|h| h.allocate_object())
+    }
+
+    /// Equivalent to [`Heap::allocate_array`](struct.Heap.html#method.allocate_array).
+    pub fn allocate_array<T: Default>(&'static self, count: usize) -> Option<&'static mut [T]> {
+        self.with(|h| h.allocate_array(count))
+    }
+
+    /// Equivalent to [`Heap::retire`](struct.Heap.html#method.retire).
+    pub fn retire(&'static self, m: Memory<'static>) {
+        self.with(|h| h.retire(m))
+    }
+
+    /// Equivalent to [`Heap::retire_object`](struct.Heap.html#method.retire_object).
+    pub fn retire_object<T>(&'static self, obj: &'static mut T) {
+        self.with(move |h| h.retire_object(obj))
+    }
+
+    /// Equivalent to [`Heap::gc`](struct.Heap.html#method.gc).
+    pub fn gc<T>(&'static self, roots: &[&T]) {
+        self.with(|h| h.gc(roots))
+    }
+
+    /// Equivalent to [`Heap::get_stats`](struct.Heap.html#method.get_stats).
+    pub fn get_stats(&'static self) -> HeapStats {
+        self.with(|h| h.get_stats())
+    }
+}
+
+impl<const N: usize> Default for StaticHeap<N> {
+    fn default() -> StaticHeap<N> {
+        StaticHeap::new()
+    }
+}
+
+/// Declare a [`StaticHeap`](struct.StaticHeap.html) of a given size in bytes,
+/// as a static:
+///
+/// ```rust
+/// mwgc::static_heap!(HEAP, 1024);
+///
+/// let n = HEAP.allocate_object::<u32>().unwrap();
+/// HEAP.gc(&[ n ]);
+/// ```
+///
+/// **Important**: The heap is for single-threaded programs. Using it from
+/// two places at once panics.
+#[macro_export]
+macro_rules! static_heap {
+    ($name: ident, $size: expr) => {
+        static $name: $crate::StaticHeap<{ $size }> = $crate::StaticHeap::new();
+    };
+}
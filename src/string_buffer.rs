@@ -4,24 +4,122 @@ use core::{fmt, str};
 pub struct StringBuffer<'a> {
     buffer: &'a mut [u8],
     index: usize,
+    truncate: bool,
+    truncated: bool,
 }
 
 impl<'a> StringBuffer<'a> {
     pub fn new(buffer: &'a mut [u8]) -> StringBuffer<'a> {
-        StringBuffer { buffer, index: 0 }
+        StringBuffer { buffer, index: 0, truncate: false, truncated: false }
+    }
+
+    /// a buffer that never reports an error: once it fills up, anything
+    /// else written is dropped (at a char boundary, so it's still valid
+    /// utf-8). after the first write that doesn't fit, every later write is
+    /// dropped too, so the result is always a prefix of what was written.
+    pub fn new_truncating(buffer: &'a mut [u8]) -> StringBuffer<'a> {
+        StringBuffer { buffer, index: 0, truncate: true, truncated: false }
     }
 
     pub fn to_str(self) -> &'a str {
         str::from_utf8(&self.buffer[0 .. self.index]).unwrap()
     }
+
+    /// borrow the string written so far, without consuming the buffer
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(&self.buffer[0 .. self.index]).unwrap()
+    }
+
+    /// bytes written so far
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.index
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.index == 0
+    }
+
+    /// total size of the backing buffer
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// bytes that can still be written before the buffer is full
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buffer.len() - self.index
+    }
+
+    /// forget everything written so far, so the buffer can be reused
+    pub fn clear(&mut self) {
+        self.index = 0;
+        self.truncated = false;
+    }
 }
 
 impl<'a> fmt::Write for StringBuffer<'a> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        let bytes = s.as_bytes();
-        if self.index + bytes.len() > self.buffer.len() { return Err(core::fmt::Error); }
+        let mut bytes = s.as_bytes();
+        if self.truncated { return Ok(()) }
+        if self.index + bytes.len() > self.buffer.len() {
+            if !self.truncate { return Err(core::fmt::Error); }
+            let mut n = self.remaining();
+            while !s.is_char_boundary(n) { n -= 1 }
+            bytes = &bytes[0 .. n];
+            self.truncated = true;
+        }
         for i in 0 .. bytes.len() { self.buffer[self.index + i] = bytes[i]; }
         self.index += bytes.len();
         Ok(())
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+    use super::StringBuffer;
+
+    #[test]
+    fn reuse() {
+        let mut buffer: [u8; 16] = [0; 16];
+        let mut b = StringBuffer::new(&mut buffer);
+        assert_eq!(b.capacity(), 16);
+        assert!(b.is_empty());
+
+        write!(b, "hello {}", 23).unwrap();
+        assert_eq!(b.as_str(), "hello 23");
+        assert_eq!(b.len(), 8);
+        assert_eq!(b.remaining(), 8);
+        assert!(write!(b, "too much text").is_err());
+
+        b.clear();
+        assert_eq!(b.remaining(), 16);
+        write!(b, "bye").unwrap();
+        assert_eq!(b.to_str(), "bye");
+    }
+
+    #[test]
+    fn truncating() {
+        let mut buffer: [u8; 8] = [0; 8];
+        let mut b = StringBuffer::new_truncating(&mut buffer);
+        write!(b, "hello there, {}", "friend").unwrap();
+        assert_eq!(b.as_str(), "hello th");
+        assert_eq!(b.remaining(), 0);
+        write!(b, "more").unwrap();
+        assert_eq!(b.as_str(), "hello th");
+
+        // don't split a multi-byte character.
+        b.clear();
+        write!(b, "wat\u{2603}\u{2603}").unwrap();
+        assert_eq!(b.as_str(), "wat\u{2603}");
+
+        // once something's been dropped, a shorter write that would fit is
+        // dropped too.
+        write!(b, "XY").unwrap();
+        assert_eq!(b.to_str(), "wat\u{2603}");
+    }
+}
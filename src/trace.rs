@@ -0,0 +1,75 @@
+use crate::BLOCK_SIZE_BYTES;
+use crate::heap::Heap;
+
+/// An object that knows which of its fields point to other objects in the
+/// heap, and can report them to a [`Marker`](struct.Marker.html). This adds
+/// edges (or extra roots) to the mark phase, for references the conservative
+/// scanner can't see: pointers held outside the heap, or stored in a form
+/// that doesn't look like an address.
+///
+/// It doesn't replace the scanner: every live object is still scanned word
+/// by word, so an integer or buffer field that looks like a pointer will
+/// still keep its target alive. To stop the scanner from reading part of an
+/// object, allocate it with
+/// [`Heap::allocate_with_scan_len`](struct.Heap.html#method.allocate_with_scan_len).
+///
+/// Here's a table with an inline array of pointers, which marks the slots
+/// that are in use:
+///
+/// ```rust
+/// use mwgc::{Heap, Marker, Trace};
+///
+/// #[derive(Default)]
+/// struct Node { value: usize }
+///
+/// #[derive(Default)]
+/// struct Table<'a> {
+///     len: usize,
+///     items: [Option<&'a Node>; 4],
+/// }
+///
+/// impl<'a> Trace for Table<'a> {
+///     fn trace<const B: usize>(&self, marker: &mut Marker<'_, '_, B>) {
+///         for item in self.items[0 .. self.len].iter().flatten() {
+///             marker.mark(*item);
+///         }
+///     }
+/// }
+///
+/// let mut data: [u8; 256] = [0; 256];
+/// let mut h = Heap::from_bytes(&mut data);
+/// let table = h.allocate_object::<Table>().unwrap();
+/// table.items[0] = Some(h.allocate_object::<Node>().unwrap());
+/// table.len = 1;
+///
+/// h.mark_start(&[ table ]);
+/// table.trace(&mut h.marker());
+/// while !h.mark_round() {}
+/// h.sweep();
+/// ```
+pub trait Trace {
+    /// Call `marker.mark(...)` on each object this object refers to. The
+    /// marker can be for a heap of any block size.
+    fn trace<const B: usize>(&self, marker: &mut Marker<'_, '_, B>);
+}
+
+/// Passed to [`Trace::trace`](trait.Trace.html#tymethod.trace) during the
+/// mark phase, to mark the objects that an object refers to. Get one from
+/// [`Heap::marker`](struct.Heap.html#method.marker).
+pub struct Marker<'a, 'heap, const BLOCK: usize = BLOCK_SIZE_BYTES> {
+    heap: &'a mut Heap<'heap, BLOCK>,
+}
+
+impl<'a, 'heap, const BLOCK: usize> Marker<'a, 'heap, BLOCK> {
+    pub(crate) fn new(heap: &'a mut Heap<'heap, BLOCK>) -> Marker<'a, 'heap, BLOCK> {
+        Marker { heap }
+    }
+
+    /// Mark an object as live. It's queued to be checked on the next
+    /// [`mark_round`](struct.Heap.html#method.mark_round), exactly as if the
+    /// garbage collector had found a pointer to it. Objects outside the
+    /// heap are ignored.
+    pub fn mark<T>(&mut self, child: &T) {
+        self.heap.check(child as *const T as *const u8);
+    }
+}
@@ -0,0 +1,31 @@
+#![cfg(feature = "allocator_api")]
+#![feature(allocator_api)]
+
+use core::cell::RefCell;
+use mwgc::{Heap, HeapAllocator, Memory};
+
+#[test]
+fn vec_in_heap() {
+    let mut data: [u8; 256] = [0; 256];
+    let heap = RefCell::new(Heap::new(Memory::new(&mut data)));
+    let alloc = HeapAllocator::new(&heap);
+    let free = heap.borrow().get_stats().free_bytes;
+
+    let mut v: Vec<u32, _> = Vec::new_in(alloc);
+    for i in 0..10 { v.push(i * 3); }
+    assert_eq!(v.iter().sum::<u32>(), 135);
+    assert!(heap.borrow().get_stats().free_bytes < free);
+    assert_eq!(v.as_ptr() as usize % core::mem::align_of::<u32>(), 0);
+
+    drop(v);
+    assert_eq!(heap.borrow().get_stats().free_bytes, free);
+}
+
+#[test]
+fn box_in_heap() {
+    let mut data: [u8; 256] = [0; 256];
+    let heap = RefCell::new(Heap::new(Memory::new(&mut data)));
+    let b = Box::new_in(12345u64, HeapAllocator::new(&heap));
+    assert_eq!(*b, 12345);
+    assert!(heap.borrow().contains(&*b));
+}
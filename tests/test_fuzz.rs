@@ -0,0 +1,59 @@
+#![cfg(feature = "fuzzing")]
+
+use mwgc::{Heap, HeapOp, Memory};
+
+// tiny xorshift, so the op stream is the same on every run.
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn op(&mut self) -> HeapOp {
+        match self.next() % 8 {
+            0 => HeapOp::Gc,
+            1 => HeapOp::Coalesce,
+            2 | 3 | 4 => HeapOp::Retire(self.next() as usize),
+            _ => HeapOp::Alloc((self.next() % 200) as usize),
+        }
+    }
+}
+
+#[test]
+fn random_ops() {
+    for seed in 1..=32 {
+        let mut data: [u8; 1024] = [0; 1024];
+        let mut h = Heap::new(Memory::new(&mut data));
+        let mut rng = Rng(seed);
+        for _ in 0..500 {
+            let op = rng.op();
+            h.apply_op(op);
+            assert_eq!(h.validate(), Ok(()), "seed {}, after {:?}", seed, op);
+        }
+    }
+}
+
+#[test]
+fn ops_track_allocations() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let free = h.get_stats().free_bytes;
+    h.apply_op(HeapOp::Alloc(32));
+    h.apply_op(HeapOp::Alloc(16));
+    assert_eq!(h.get_stats().free_bytes, free - 48);
+
+    // both allocations are roots, so neither is collected.
+    h.apply_op(HeapOp::Gc);
+    assert_eq!(h.get_stats().free_bytes, free - 48);
+
+    h.apply_op(HeapOp::Retire(0));
+    h.apply_op(HeapOp::Retire(16 + 1));
+    h.apply_op(HeapOp::Retire(1));
+    h.apply_op(HeapOp::Coalesce);
+    assert_eq!(h.get_stats().free_bytes, free);
+    assert_eq!(h.validate(), Ok(()));
+}
@@ -0,0 +1,31 @@
+use mwgc::{Heap, Memory};
+
+#[test]
+fn allocate_str() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let s1 = h.allocate_str("hello").unwrap();
+    let s2 = h.allocate_str("a string longer than one block").unwrap();
+    let s3 = h.allocate_str("").unwrap();
+    assert_eq!(s1, "hello");
+    assert_eq!(s2, "a string longer than one block");
+    assert_eq!(s3, "");
+    assert_eq!(h.get_stats().free_bytes, 240 - 16 - 32 - 16);
+}
+
+#[test]
+fn gc_str() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let s1 = h.allocate_gc_str("toaster").unwrap();
+    let _s2 = h.allocate_gc_str("dead symbol").unwrap();
+    let s3 = h.allocate_gc_str("").unwrap();
+    assert_eq!(s1.as_str(), "toaster");
+    assert_eq!(&**s3, "");
+    assert_eq!(s1.len(), 7);
+
+    h.gc(&[ s1, s3 ]);
+    let mut buffer: [u8; 256] = [0; 256];
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
+    assert_eq!(s1.as_str(), "toaster");
+}
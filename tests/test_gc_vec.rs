@@ -0,0 +1,56 @@
+use mwgc::{GcVec, Heap, Memory};
+
+#[test]
+fn push_and_pop() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut v = GcVec::<u32>::new();
+    assert_eq!(v.capacity(), 0);
+    assert!(v.pop().is_none());
+
+    // the first allocation is rounded up to a whole block.
+    v.push(&mut h, 10).unwrap();
+    assert_eq!(v.capacity(), 4);
+    for i in 1..4 { v.push(&mut h, 10 + i).unwrap(); }
+    assert_eq!(h.get_stats().free_bytes, 224);
+
+    // growing moves to a new buffer and frees the old one.
+    let old = v.as_ptr();
+    v.push(&mut h, 14).unwrap();
+    assert_ne!(v.as_ptr(), old);
+    assert_eq!(v.capacity(), 8);
+    assert_eq!(v.len(), 5);
+    assert_eq!(h.get_stats().free_bytes, 208);
+    assert_eq!(v.as_slice(), &[ 10, 11, 12, 13, 14 ]);
+
+    v[0] = 9;
+    assert_eq!(v[0], 9);
+    assert_eq!(v.pop(), Some(14));
+    assert_eq!(v.len(), 4);
+}
+
+#[test]
+fn full_heap() {
+    let mut data: [u8; 64] = [0; 64];
+    let mut h = Heap::new(Memory::new(&mut data));
+    assert_eq!(h.get_stats().total_bytes, 48);
+    let mut v = GcVec::<u64>::new();
+    for i in 1..5 { v.push(&mut h, i).unwrap(); }
+    assert_eq!(v.capacity(), 4);
+    assert_eq!(v.push(&mut h, 5), Err(5));
+    assert_eq!(v.as_slice(), &[ 1, 2, 3, 4 ]);
+}
+
+#[test]
+fn survives_gc() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let v = h.allocate_object::<GcVec<u64>>().unwrap();
+    for i in 0..5 { v.push(&mut h, i).unwrap(); }
+    let _garbage = h.allocate(32).unwrap();
+
+    h.gc(&[ v ]);
+    let mut buffer: [u8; 256] = [0; 256];
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
+    assert_eq!(v.as_slice(), &[ 0, 1, 2, 3, 4 ]);
+}
@@ -0,0 +1,16 @@
+#![cfg(feature = "critical-section")]
+
+use mwgc::{Heap, LockedHeap, Memory};
+
+#[test]
+fn locked_heap() {
+    let mut data: [u8; 256] = [0; 256];
+    let h = LockedHeap::new(Heap::new(Memory::new(&mut data)));
+    let o1 = h.allocate_object::<u64>().unwrap();
+    let _o2 = h.allocate_object::<u64>().unwrap();
+    assert_eq!(h.get_stats().free_bytes, 208);
+
+    h.gc(&[ o1 ]);
+    assert_eq!(h.get_stats().free_bytes, 224);
+    assert_eq!(h.lock(|heap| heap.size_of(o1)), 16);
+}
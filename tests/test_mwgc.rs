@@ -1,5 +1,7 @@
 use core::mem;
-use mwgc::{Heap, Memory};
+use mwgc::{Color, GcError, Heap, HeapInitError, HeapRef, Marker, Memory, MIN_BLOCK_SIZE, SpanKind, Trace};
+#[cfg(feature = "observer")]
+use mwgc::GcObserver;
 
 #[repr(align(8))]
 struct Blob {
@@ -8,6 +10,10 @@ struct Blob {
 
 static mut DATA: Blob = Blob { data: [0; 256] };
 
+// bytes at the start of each free span that hold the free-list link (and
+// the canary check word, with the `canary` feature).
+const FREE_HEADER: usize = MIN_BLOCK_SIZE + if cfg!(feature = "canary") { mem::size_of::<usize>() } else { 0 };
+
 // used to test the GC
 #[derive(Default)]
 struct Sample<'a> {
@@ -37,7 +43,7 @@ fn new_heap() {
     assert_eq!(h.get_stats().end, unsafe { h.get_stats().start.offset(240) });
 
     let mut buffer: [u8; 256] = [0; 256];
-    assert_eq!(h.dump_into(&mut buffer), "FREE[240]");
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "FREE[240]");
 }
 
 #[test]
@@ -50,10 +56,58 @@ fn allocate() {
         assert_eq!(m.len(), 32);
 
         let mut buffer: [u8; 256] = [0; 256];
-        assert_eq!(h.dump_into(&mut buffer), "Blue[32], FREE[208]");
+        assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[32], FREE[208]");
     }
 }
 
+#[test]
+fn spans() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let m1 = h.allocate(32).unwrap();
+    let _m2 = h.allocate(16).unwrap();
+    h.retire(m1);
+
+    let (start, _) = h.get_live_range();
+    let mut spans = h.spans();
+    let s1 = spans.next().unwrap();
+    assert_eq!((s1.start as usize, s1.len, s1.kind), (start, 32, SpanKind::Free));
+    let s2 = spans.next().unwrap();
+    assert_eq!((s2.start as usize, s2.len, s2.kind), (start + 32, 16, SpanKind::Live(Color::Blue)));
+    let s3 = spans.next().unwrap();
+    assert_eq!((s3.start as usize, s3.len, s3.kind), (start + 48, 192, SpanKind::Free));
+    assert!(spans.next().is_none());
+}
+
+#[test]
+fn dump_truncated() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    h.allocate(32).unwrap();
+    h.allocate(32).unwrap();
+
+    let mut buffer: [u8; 256] = [0; 256];
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[32], Blue[32], FREE[176]");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, Blue, FREE");
+
+    let mut small: [u8; 10] = [0; 10];
+    assert!(h.dump_into(&mut small).is_err());
+    assert!(h.dump_spans_into(&mut small).is_err());
+}
+
+#[test]
+fn display() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_object::<Sample>().unwrap();
+    h.mark_start(&[ o1 ]);
+
+    let mut buffer: [u8; 256] = [0; 256];
+    assert_eq!(format!("{}", h), h.dump_spans_into(&mut buffer).unwrap());
+    assert_eq!(format!("{}", h), "Check, Blue, FREE");
+}
+
 #[test]
 fn allocate_array() {
     let mut data: [u8; 256] = [0; 256];
@@ -65,7 +119,7 @@ fn allocate_array() {
 
         // multiple of 16:
         let mut buffer: [u8; 256] = [0; 256];
-        assert_eq!(h.dump_into(&mut buffer), "Blue[48], FREE[192]");
+        assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[48], FREE[192]");
     }
 }
 
@@ -77,17 +131,124 @@ fn retire() {
     let m1 = h.allocate(32).unwrap();
     let m2 = h.allocate(32).unwrap();
     h.retire(m1);
-    assert_eq!(h.dump_into(&mut buffer), "FREE[32], Blue[32], FREE[176]");
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "FREE[32], Blue[32], FREE[176]");
     h.retire(m2);
-    assert_eq!(h.dump_into(&mut buffer), "FREE[240]");
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "FREE[240]");
 
     let m3 = h.allocate_object::<Sample>().unwrap();
     assert_eq!(
-        h.dump_into(&mut buffer),
+        h.dump_into(&mut buffer).unwrap(),
         format!("Blue[{}], FREE[{}]", mem::size_of::<Sample>(), 240 - mem::size_of::<Sample>())
     );
     h.retire_object(m3);
-    assert_eq!(h.dump_into(&mut buffer), "FREE[240]");
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "FREE[240]");
+}
+
+#[test]
+fn reset() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut buffer: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_object::<Sample>().unwrap();
+    h.allocate(64).unwrap();
+    h.mark_start(&[ o1 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Blue, Blue, FREE");
+
+    h.reset();
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "FREE[240]");
+    assert_eq!(h.get_stats().free_bytes, 240);
+
+    // and it's usable again.
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    h.gc(&[ o2 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE");
+}
+
+#[test]
+fn extend() {
+    let mut data: [u8; 512] = [0; 512];
+    let mut buffer: [u8; 256] = [0; 256];
+    let (m1, m2) = Memory::new(&mut data).split_at(256);
+    let (m2, m3) = m2.split_at(128);
+    let mut h = Heap::new(m1);
+    let o1 = h.allocate(160).unwrap();
+    h.allocate(64).unwrap();
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[160], Blue[64], FREE[16]");
+    assert!(h.allocate(64).is_none());
+
+    // must be adjacent:
+    assert!(!h.extend(m3));
+    assert!(h.extend(m2));
+    assert_eq!(h.get_stats().total_bytes, 368);
+    assert_eq!(h.get_stats().overhead_bytes, 384 - 368);
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[160], Blue[64], FREE[144]");
+
+    let o3 = h.allocate(64).unwrap();
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[160], Blue[64], Blue[64], FREE[80]");
+    h.retire(o1);
+    h.retire(o3);
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "FREE[160], Blue[64], FREE[144]");
+}
+
+#[test]
+fn contiguous_free() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    assert_eq!(h.contiguous_free(), 240);
+
+    let m1 = h.allocate(64).unwrap();
+    h.allocate(32).unwrap();
+    h.retire(m1);
+    assert_eq!(h.get_stats().free_bytes, 208);
+    assert_eq!(h.contiguous_free(), 144);
+    assert!(h.allocate(145).is_none());
+    assert!(h.allocate(144).is_some());
+}
+
+#[test]
+fn alloc_size_bounds() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    assert_eq!(h.min_alloc_size(), 16);
+    assert_eq!(h.max_alloc_size(), h.get_stats().total_bytes);
+    assert_eq!(h.max_alloc_size(), 240);
+
+    let m = h.allocate(0).unwrap();
+    assert_eq!(m.len(), h.min_alloc_size());
+    h.retire(m);
+    assert!(h.allocate(h.max_alloc_size() + 1).is_none());
+    assert!(h.allocate(h.max_alloc_size()).is_some());
+    assert_eq!(h.max_alloc_size(), 240);
+}
+
+#[test]
+fn split_memory() {
+    let mut blob = Blob { data: [0; 256] };
+    let data = &mut blob.data;
+    let mut colors: [u8; 4] = [0; 4];
+    let mut buffer: [u8; 256] = [0; 256];
+
+    // too small:
+    assert!(Heap::with_split_memory(Memory::new(data), Memory::new(&mut colors[0 .. 3])).is_none());
+    assert!(Heap::with_split_memory(Memory::new(&mut data[0 .. 15]), Memory::new(&mut colors)).is_none());
+
+    // 256 bytes is exactly 16 blocks, so no room is lost to the color map.
+    let mut h = Heap::with_split_memory(Memory::new(data), Memory::new(&mut colors)).unwrap();
+    assert_eq!(h.get_stats().total_bytes, 256);
+
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let _o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    o1.p = Some(o3);
+    h.mark_start(&[ o1 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Blue, Blue, FREE");
+    assert!(!h.mark_round());
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Blue, Check, FREE");
+    assert!(h.mark_round());
+    h.sweep();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
+    assert_eq!(h.get_stats().free_bytes, 256 - 2 * mem::size_of::<Sample>());
 }
 
 #[test]
@@ -101,10 +262,10 @@ fn mark_simple() {
     let o5 = h.allocate_object::<Sample>().unwrap();
 
     let mut buffer: [u8; 256] = [0; 256];
-    assert_eq!(h.dump_spans_into(&mut buffer), "Blue, Blue, Blue, Blue, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, Blue, Blue, Blue, Blue, FREE");
 
     // leave o3 stranded. make o1 point to o2, which points to o4, o5, and back to o1.
-    o4.p = Some(unsafe { &*(455 as *const Sample) });
+    o4.p = Some(unsafe { &*(456 as *const Sample) });
     o5.number = 23;
     o2.p = Some(o4);
     o2.next = Some(o5);
@@ -113,19 +274,19 @@ fn mark_simple() {
 
     h.mark_start(&[ o1 ]);
     assert_eq!(h.get_mark_range(), (o1.ptr(), o1.ptr()));
-    assert_eq!(h.dump_spans_into(&mut buffer), "Check, Blue, Blue, Blue, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Blue, Blue, Blue, Blue, FREE");
 
     assert!(!h.mark_round());
     assert_eq!(h.get_mark_range(), (o2.ptr(), o2.ptr()));
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Check, Blue, Blue, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Check, Blue, Blue, Blue, FREE");
 
     assert!(!h.mark_round());
     assert_eq!(h.get_mark_range(), (o4.ptr(), o5.ptr()));
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Green, Blue, Check, Check, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Blue, Check, Check, FREE");
 
     assert!(h.mark_round());
     assert_eq!(h.get_mark_range(), (core::ptr::null(), core::ptr::null()));
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Green, Blue, Green, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Blue, Green, Green, FREE");
 }
 
 #[test]
@@ -139,19 +300,19 @@ fn sweep_simple() {
     let _o5 = h.allocate_object::<Sample>().unwrap();
 
     let mut buffer: [u8; 256] = [0; 256];
-    assert_eq!(h.dump_spans_into(&mut buffer), "Blue, Blue, Blue, Blue, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, Blue, Blue, Blue, Blue, FREE");
 
     o1.p = Some(o3);
     h.mark(&[ o1 ]);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Blue, Green, Blue, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Blue, Green, Blue, Blue, FREE");
     h.sweep();
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, FREE, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
 
     o1.p = None;
     h.mark(&[ o1 ]);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Blue, FREE, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, FREE, Green, FREE");
     h.sweep();
-    assert_eq!(h.dump_spans_into(&mut buffer), "Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, FREE");
 }
 
 #[test]
@@ -168,24 +329,24 @@ fn alloc_during_collection() {
     o1.p = Some(o2);
 
     h.mark_start(&[ o1 ]);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Check, Blue, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Blue, Blue, FREE");
 
     assert_eq!(h.mark_round(), false);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Check, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Check, Blue, FREE");
 
     // o1 is saved, o2 will be checked on the next round. so, let's
     // allocate an o4, and move the links to be: o2 -> o4 -> o3.
     let o4 = h.allocate_object::<Sample>().unwrap();
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Check, Blue, Check, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Check, Blue, Check, FREE");
     o4.p = Some(o3);
     let o2 = o1.p.take().unwrap().as_mut();
     o2.p = Some(o4);
 
     assert_eq!(h.mark_round(), false);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Green, Check, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Check, Green, FREE");
 
     assert_eq!(h.mark_round(), true);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Green, Green, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Green, Green, FREE");
 }
 
 #[test]
@@ -202,7 +363,27 @@ fn inner_pointer() {
     o1.p = Some(inside_o3);
 
     h.gc(&[ o1 ]);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, FREE, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
+}
+
+#[test]
+fn check_span_grows_both_ways() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    let o4 = h.allocate_object::<Sample>().unwrap();
+
+    // the check span starts in the middle, then grows down, then up.
+    h.mark_start(&[ o2 ]);
+    h.mark_check(o1);
+    h.mark_check(o4);
+    h.mark_check(o3);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Check, Check, Check, FREE");
+    assert_eq!(h.mark_round(), true);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Green, Green, FREE");
 }
 
 #[test]
@@ -219,10 +400,10 @@ fn ref_moves_backward() {
     o1.p = Some(o2);
 
     h.mark_start(&[ o1 ]);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Check, Blue, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Blue, Blue, FREE");
 
     assert_eq!(h.mark_round(), false);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Check, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Check, Blue, FREE");
 
     // suddenly it's o1 -> o3 -> o2.
     let o2 = o1.p.take().unwrap().as_mut();
@@ -230,13 +411,1760 @@ fn ref_moves_backward() {
     o3.p = Some(o2);
     o1.p = Some(o3);
     h.mark_check(o1);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Check, Check, Blue, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Check, Blue, FREE");
 
     assert_eq!(h.mark_round(), false);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Green, Check, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Check, FREE");
 
     assert_eq!(h.mark_round(), true);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, Green, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Green, FREE");
+}
+
+#[test]
+fn tagged_pointers() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut buffer: [u8; 256] = [0; 256];
+
+    // o1 -> o2 -> o3, using 3-bit tags.
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    o1.number = (o2.ptr() as usize) | 5;
+    o2.number = (o3.ptr() as usize) | 3;
+
+    h.set_pointer_mask(7);
+    h.gc(&[ o1 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Green, FREE");
+
+    // without the mask, the tagged words aren't pointers.
+    h.set_pointer_mask(0);
+    h.gc(&[ o1 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, FREE");
+}
+
+#[test]
+fn handles() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    o2.number = 23;
+
+    let h1 = h.handle_of(o1).unwrap();
+    let h2 = h.handle_of(o2).unwrap();
+    assert_eq!(h1, HeapRef(0));
+    assert_eq!(h.resolve::<Sample>(h2).unwrap().number, 23);
+    assert_eq!(h.resolve::<Sample>(h1).unwrap().ptr(), o1.ptr());
+
+    // not the start of an allocation:
+    let inside = unsafe { &*(((o1 as *const Sample as usize) + 16) as *const Sample) };
+    assert!(h.handle_of(inside).is_none());
+    assert!(h.resolve::<Sample>(HeapRef(1)).is_none());
+    assert!(h.resolve::<Sample>(HeapRef(1000)).is_none());
+
+    // dead objects don't resolve.
+    h.gc(&[ o2 ]);
+    assert!(h.resolve::<Sample>(h1).is_none());
+    assert_eq!(h.resolve::<Sample>(h2).unwrap().number, 23);
+}
+
+#[test]
+fn snapshot() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<u64>().unwrap();
+    let o2 = h.allocate_array::<u8>(20).unwrap();
+    let o3 = h.allocate_object::<u32>().unwrap();
+    *o1 = 0x0102030405060708;
+    o2[0] = 0xff;
+    o2[19] = 0xee;
+    h.retire_object(o3);
+
+    let mut out = String::new();
+    h.snapshot(&mut out).unwrap();
+    let start = h.get_live_range().0;
+    assert_eq!(out, format!(
+        "mwgc snapshot start={:#x} bytes=240 block=16\n\
+        0 16 Blue {:016x}0000000000000000\n\
+        16 32 Blue ff{}ee000000000000000000000000\n",
+        start, u64::from_be_bytes(0x0102030405060708u64.to_le_bytes()), "00".repeat(18)
+    ));
+}
+
+#[test]
+fn to_dot() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+
+    // o1 -> o2 <-> o3, and o4 is garbage.
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    let _o4 = h.allocate_array::<u8>(16).unwrap();
+    o3.p = Some(unsafe { &*(o2 as *const Sample) });
+    o2.p = Some(o3);
+    o1.p = Some(o2);
+
+    let (n2, n3, n4) = (mem::size_of::<Sample>(), mem::size_of::<Sample>() * 2, mem::size_of::<Sample>() * 3);
+    let mut out = String::new();
+    h.to_dot(&[ o1 ], &mut out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines, [
+        "digraph mwgc {".to_string(),
+        "  roots [shape=box];".to_string(),
+        "  roots -> n0;".to_string(),
+        format!("  n0 [label=\"0 ({})\"];", n2),
+        format!("  n0 -> n{};", n2),
+        format!("  n{} [label=\"{} ({})\"];", n2, n2, n2),
+        format!("  n{} -> n{};", n2, n3),
+        format!("  n{} [label=\"{} ({})\"];", n3, n3, n2),
+        format!("  n{} -> n{};", n3, n2),
+        format!("  n{} [label=\"{} (16)\"];", n4, n4),
+        "}".to_string(),
+    ]);
+}
+
+#[test]
+fn colormap_bytes() {
+    let mut data: [u8; 256] = [0; 256];
+    let start = &data[0] as *const u8 as usize;
+    let mut h = Heap::new(Memory::new(&mut data));
+    h.allocate(32).unwrap();
+    h.allocate(16).unwrap();
+
+    // 4 bytes are reserved at the end of the 256-byte buffer.
+    assert_eq!(h.colormap_offset(), 252);
+    assert_eq!(h.colormap_offset() + h.get_live_range().0, start + 252);
+    assert_eq!(h.colormap_bytes(), &[ 0b11_01_00_01, 0xff, 0xff, 0xff ]);
+}
+
+#[derive(Default)]
+struct Table<'a> {
+    count: usize,
+    items: [Option<&'a Sample<'a>>; 3],
+}
+
+impl<'a> Trace for Table<'a> {
+    fn trace<const B: usize>(&self, marker: &mut Marker<'_, '_, B>) {
+        for item in self.items[0 .. self.count].iter().flatten() {
+            marker.mark(*item);
+        }
+    }
+}
+
+#[test]
+fn marker() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut buffer: [u8; 256] = [0; 256];
+
+    // only the first two items are traced.
+    let table = h.allocate_object::<Table>().unwrap();
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    o2.p = Some(o3);
+    table.items = [ Some(o1), Some(o2), Some(o3) ];
+    table.count = 2;
+
+    // the table itself isn't a root.
+    h.mark_start::<Sample>(&[]);
+    table.trace(&mut h.marker());
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, Check, Check, Blue, FREE");
+    while !h.mark_round() {}
+    h.sweep();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE, Green, Green, Green, FREE");
+}
+
+#[test]
+fn marker_block_size() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::<32>::with_block_size(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+
+    let table = h.allocate_object::<Table>().unwrap();
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    table.items = [ Some(o1), None, None ];
+    table.count = 1;
+
+    h.mark_start::<Sample>(&[]);
+    table.trace(&mut h.marker());
+    while !h.mark_round() {}
+    h.sweep();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE, Green, FREE");
+}
+
+#[cfg(feature = "observer")]
+#[derive(Default)]
+struct Observer {
+    marks: usize,
+    spans: usize,
+    span_bytes: usize,
+    reclaimed: usize,
+}
+
+#[cfg(feature = "observer")]
+impl GcObserver for Observer {
+    fn on_mark_start(&mut self) {
+        self.marks += 1;
+    }
+
+    fn on_sweep_span(&mut self, _start: *const u8, len: usize) {
+        self.spans += 1;
+        self.span_bytes += len;
+    }
+
+    fn on_gc_end(&mut self, reclaimed: usize) {
+        self.reclaimed += reclaimed;
+    }
+}
+
+#[cfg(feature = "observer")]
+#[test]
+fn observer() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut observer = Observer::default();
+    {
+        let mut h = Heap::new(Memory::new(&mut data));
+        h.set_observer(Some(&mut observer));
+        let o1 = h.allocate_object::<Sample>().unwrap();
+        h.allocate(32).unwrap();
+        h.allocate_object::<Sample>().unwrap();
+        h.allocate(16).unwrap();
+        h.gc(&[ o1 ]);
+        h.gc(&[ o1 ]);
+    }
+    assert_eq!(observer.marks, 2);
+    assert_eq!(observer.spans, 3);
+    assert_eq!(observer.span_bytes, 48 + mem::size_of::<Sample>());
+    assert_eq!(observer.reclaimed, observer.span_bytes);
+}
+
+#[test]
+fn estimate_garbage() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut buffer: [u8; 256] = [0; 256];
+
+    // o1 -> o3 -> o4, with o2 and o5 as garbage.
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let _o2 = h.allocate(48).unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    let o4 = h.allocate_object::<Sample>().unwrap();
+    let _o5 = h.allocate(16).unwrap();
+    o3.next = Some(o4);
+    o1.p = Some(o3);
+
+    let mut scratch: [u8; 4] = [0; 4];
+    assert_eq!(h.garbage_estimate_scratch_len(), 4);
+    assert_eq!(h.estimate_garbage(&[ o1 ], &mut scratch), Ok(64));
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, Blue, Blue, Blue, Blue, FREE");
+
+    let before = h.get_stats().free_bytes;
+    h.gc(&[ o1 ]);
+    assert_eq!(h.get_stats().free_bytes - before, 64);
+    assert_eq!(h.estimate_garbage(&[ o1 ], &mut scratch), Ok(0));
+    assert_eq!(h.estimate_garbage::<Sample>(&[], &mut scratch), Ok(3 * mem::size_of::<Sample>()));
+
+    let mut small: [u8; 3] = [0; 3];
+    assert_eq!(h.estimate_garbage(&[ o1 ], &mut small), Err(GcError::ScratchTooSmall));
+    h.mark_start(&[ o1 ]);
+    assert_eq!(h.estimate_garbage(&[ o1 ], &mut scratch), Err(GcError::WrongPhase));
+}
+
+#[cfg(feature = "scan_limits")]
+#[test]
+fn scan_len() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let words = 64 / mem::size_of::<usize>();
+
+    // o1 has a real pointer to o2 in its header, and payload that looks
+    // like a pointer to o3.
+    let m = h.allocate_with_scan_len(64, 16).unwrap();
+    let o1: &mut [usize] = unsafe { core::slice::from_raw_parts_mut(m.start() as *mut usize, words) };
+    let o2 = h.allocate(16).unwrap();
+    let o3 = h.allocate(16).unwrap();
+    o1[0] = o2.start() as usize;
+    o1[words - 1] = o3.start() as usize;
+    let (o2, o3) = (o2.start(), o3.start());
+
+    h.gc(&[ &o1[0] ]);
+    assert!(h.lookup(o2).is_some());
+    assert!(h.lookup(o3).is_none());
+    assert_eq!(h.get_stats().free_bytes, 240 - 80);
+
+    // once o1 is freed, its scan length is forgotten, so a new object in the
+    // same spot is scanned in full.
+    h.retire(m);
+    let m = h.allocate(64).unwrap();
+    let o1: &mut [usize] = unsafe { core::slice::from_raw_parts_mut(m.start() as *mut usize, words) };
+    let o3 = h.allocate(16).unwrap();
+    o1[0] = 0;
+    o1[words - 1] = o3.start() as usize;
+    h.gc(&[ &o1[0] ]);
+    assert!(h.lookup(o3.start()).is_some());
+
+    // only a few objects can have a scan length.
+    let free = h.get_stats().free_bytes;
+    for _ in 0..4 { h.allocate_with_scan_len(16, 8).unwrap(); }
+    assert!(h.allocate_with_scan_len(16, 8).is_none());
+    assert_eq!(h.get_stats().free_bytes, free - 64);
+}
+
+#[cfg(feature = "scan_limits")]
+#[test]
+fn scan_len_partial_word() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut scratch: [u8; 4] = [0; 4];
+
+    // a scan length of 1 still covers the whole first word, when marking
+    // and when estimating.
+    let m = h.allocate_with_scan_len(32, 1).unwrap();
+    let o1 = unsafe { &mut *(m.start() as *mut usize) };
+    let o2 = h.allocate(16).unwrap();
+    let _o3 = h.allocate(16).unwrap();
+    *o1 = o2.start() as usize;
+
+    assert_eq!(h.estimate_garbage(&[ &*o1 ], &mut scratch), Ok(16));
+    let before = h.get_stats().free_bytes;
+    h.gc(&[ &*o1 ]);
+    assert_eq!(h.get_stats().free_bytes - before, 16);
+    assert!(h.lookup(o2.start()).is_some());
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn marked_objects() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    assert_eq!(h.get_stats().marked_objects, 0);
+
+    // a cycle o1 -> o2 -> o3 -> o1, with o3 also shared by o1, and o4 as garbage.
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    let _o4 = h.allocate_object::<Sample>().unwrap();
+    o3.next = Some(unsafe { &*(o1 as *const Sample) });  // trick rust into making a circ ref
+    o2.next = Some(o3);
+    o1.next = Some(o2);
+    o1.p = Some(o3);
+
+    h.gc(&[ o1, o2 ]);
+    assert_eq!(h.get_stats().marked_objects, 3);
+
+    // an object that's checked again after it's been scanned isn't counted twice.
+    h.mark_start(&[ o1 ]);
+    h.mark_round();
+    h.mark_check(o1);
+    while !h.mark_round() {}
+    h.sweep();
+    assert_eq!(h.get_stats().marked_objects, 3);
+
+    h.gc::<Sample>(&[]);
+    assert_eq!(h.get_stats().marked_objects, 0);
+}
+
+#[cfg(feature = "pinning")]
+#[test]
+fn free_color() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate_pinned::<Sample>().unwrap();
+    h.allocate(32).unwrap();
+
+    // between collections, every live object has the current color.
+    let old = h.current_color();
+    assert_eq!(h.free_color(old.opposite()), Ok(0));
+    assert_eq!(h.free_color(Color::Check), Ok(0));
+    assert_eq!(h.get_stats().free_bytes, 240 - mem::size_of::<Sample>() - 32);
+
+    // flip colors with a collection: o1 survives as a pin, and the new
+    // object has the new color.
+    h.gc::<Sample>(&[]);
+    let o2 = h.allocate(16).unwrap();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, FREE");
+    assert_eq!(h.free_color(Color::Green), Ok(mem::size_of::<Sample>() + 16));
+    assert_eq!(h.get_stats().free_bytes, 240);
+    assert!(!h.unpin(o1));
+    assert!(h.lookup(o2.start()).is_none());
+
+    h.mark_start::<Sample>(&[]);
+    assert_eq!(h.free_color(Color::Green), Err(GcError::WrongPhase));
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn peak_live_bytes() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let m = h.allocate(64).unwrap();
+    h.retire(m);
+    assert_eq!(h.get_stats().peak_live_bytes, mem::size_of::<Sample>() + 64);
+
+    for _ in 0..4 { h.allocate(32).unwrap(); }
+    let peak = mem::size_of::<Sample>() + 128;
+    assert_eq!(h.get_stats().peak_live_bytes, peak);
+
+    h.gc(&[ o1 ]);
+    assert_eq!(h.get_stats().free_bytes, 240 - mem::size_of::<Sample>());
+    assert_eq!(h.get_stats().peak_live_bytes, peak);
+
+    // it only goes up once the old peak is passed.
+    h.allocate(128).unwrap();
+    assert_eq!(h.get_stats().peak_live_bytes, peak);
+    h.allocate(16).unwrap();
+    assert_eq!(h.get_stats().peak_live_bytes, peak + 16);
+}
+
+#[test]
+fn zero_sized_object() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let free = h.get_stats().free_bytes;
+    let unit = h.allocate_object::<()>().unwrap();
+    assert_eq!(h.get_stats().free_bytes, free);
+    h.retire_object(unit);
+    assert_eq!(h.get_stats().free_bytes, free);
+}
+
+#[test]
+fn allocation_size() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let array = h.allocate_array::<u32>(10).unwrap();
+    assert_eq!(h.size_of(&array[0]), 48);
+    assert_eq!(h.size_of(&array[3]), 48);
+    assert_eq!(h.allocation_size(&array[9] as *const u32 as *const u8), 48);
+    let outside = 0u32;
+    assert_eq!(h.allocation_size(&outside as *const u32 as *const u8), 0);
+}
+
+#[test]
+fn validate() {
+    let mut data = Blob { data: [0; 256] };
+    let start = data.data.as_ptr() as usize;
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_object::<Sample>().unwrap();
+    assert_eq!(h.validate(), Ok(()));
+    h.gc(&[ o1 ]);
+    assert_eq!(h.validate(), Ok(()));
+
+    // scribble a "continue" over block 0.
+    let offset = h.colormap_offset();
+    unsafe { *((start + offset) as *mut u8) &= !3 };
+    assert_eq!(h.validate(), Err("block 0 is marked as a continuation"));
+}
+
+#[test]
+fn allocatable_bytes() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    assert_eq!(h.allocatable_bytes(), h.get_stats().free_bytes);
+
+    // chop the heap into single-block slivers.
+    let mut slivers = Vec::new();
+    while let Some(m) = h.allocate(16) { slivers.push(m); }
+    let count = slivers.len();
+    for m in slivers.into_iter().step_by(2) { h.retire(m); }
+    assert_eq!(h.allocatable_bytes(), h.get_stats().free_bytes);
+
+    // and every one of those bytes really can be allocated.
+    let mut allocated = 0;
+    while h.allocate(16).is_some() { allocated += 16; }
+    assert_eq!(allocated, (count + 1) / 2 * 16);
+    assert_eq!(h.allocatable_bytes(), 0);
+}
+
+#[test]
+fn retire_array() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let free = h.get_stats().free_bytes;
+    let array = h.allocate_array::<u32>(10).unwrap();
+    assert_eq!(h.get_stats().free_bytes, free - 48);
+    h.retire_array(array);
+    assert_eq!(h.get_stats().free_bytes, free);
+    assert_eq!(h.contiguous_free(), free);
+}
+
+#[test]
+#[should_panic(expected = "retired memory isn't inside this heap")]
+fn retire_foreign_memory() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut stack: [u8; 64] = [0; 64];
+    let mut h = Heap::new(Memory::new(&mut data));
+    h.retire(Memory::new(&mut stack));
+}
+
+#[test]
+#[should_panic(expected = "retired memory isn't inside this heap")]
+fn retire_foreign_object() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut stack = Sample::default();
+    let mut h = Heap::new(Memory::new(&mut data));
+    h.retire_object(&mut stack);
+}
+
+#[test]
+fn lookup() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    h.allocate(16).unwrap();
+    let array = h.allocate_array::<u32>(10).unwrap();
+    let base = array.as_ptr() as *const u8;
+    let color = h.lookup(base).unwrap().2;
+    assert_eq!(h.lookup(base), Some((base, 48, color)));
+    assert_eq!(h.lookup(&array[9] as *const u32 as *const u8), Some((base, 48, color)));
+    assert_eq!(h.lookup(base.wrapping_add(48)), None);
+
+    let outside = 0u8;
+    assert_eq!(h.lookup(&outside), None);
+    assert_eq!(h.lookup(core::ptr::null()), None);
+}
+
+#[test]
+fn mark_slice() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut buffer: [u8; 256] = [0; 256];
+
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    o1.p = Some(o3);
+    let registers: [usize; 4] = [ 0, 23, o1.ptr() as usize, usize::MAX ];
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, Blue, Blue, FREE");
+
+    h.mark_start::<Sample>(&[]);
+    h.mark_slice(&registers);
+    while !h.mark_round() {}
+    h.sweep();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
+    assert!(h.lookup(o2.ptr()).is_none());
+}
+
+#[test]
+fn cross_heap_pointers() {
+    let mut data_a = Blob { data: [0; 256] };
+    let mut data_b = Blob { data: [0; 256] };
+    let mut a = Heap::new(Memory::new(&mut data_a.data));
+    let mut b = Heap::new(Memory::new(&mut data_b.data));
+    let mut buffer: [u8; 256] = [0; 256];
+
+    let a1 = a.allocate_object::<Sample>().unwrap();
+    let a2 = a.allocate_object::<Sample>().unwrap();
+    let b1 = b.allocate_object::<Sample>().unwrap();
+    let b2 = b.allocate_object::<Sample>().unwrap();
+    b1.p = Some(a2);
+    a1.p = Some(b1);
+
+    // collecting A mustn't touch B, and the pointer from B doesn't keep a2 alive.
+    a.gc(&[ a1 ]);
+    assert_eq!(a.dump_spans_into(&mut buffer).unwrap(), "Green, FREE");
+    assert_eq!(b.dump_spans_into(&mut buffer).unwrap(), "Blue, Blue, FREE");
+    assert_eq!(a.size_of(b1), 0);
+
+    // and vice versa.
+    b.gc(&[ b2 ]);
+    assert_eq!(b.dump_spans_into(&mut buffer).unwrap(), "FREE, Green, FREE");
+    assert_eq!(a.dump_spans_into(&mut buffer).unwrap(), "Green, FREE");
+
+    // checking a foreign object during marking is ignored.
+    a.mark_start(&[ a1 ]);
+    a.mark_check(b2);
+    while !a.mark_round() {}
+    a.sweep();
+    assert_eq!(a.dump_spans_into(&mut buffer).unwrap(), "Blue, FREE");
+    assert_eq!(b.dump_spans_into(&mut buffer).unwrap(), "FREE, Green, FREE");
+}
+
+#[test]
+fn contains() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let array = h.allocate_array::<u32>(10).unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let outside = Sample::default();
+    assert!(h.contains(o1));
+    assert!(h.contains(&array[0]));
+    assert!(!h.contains(&array[4]));
+    assert!(!h.contains(&o1.number));
+    assert!(!h.contains(&outside));
+
+    let p = o2.ptr();
+    h.retire_object(o2);
+    assert!(h.is_ptr_inside(p));
+    assert!(!h.contains(unsafe { &*(p as *const Sample) }));
+}
+
+#[test]
+fn allocate_high() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut short = Vec::new();
+    for _ in 0..3 {
+        short.push(h.allocate(16).unwrap());
+        h.allocate(16).unwrap();
+    }
+    for m in short.drain(..) { h.retire(m); }
+    assert_eq!(h.contiguous_free(), 144);
+    h.reset();
+
+    for _ in 0..3 {
+        short.push(h.allocate(16).unwrap());
+        h.allocate_high(16).unwrap();
+    }
+    for m in short.drain(..) { h.retire(m); }
+    assert_eq!(h.contiguous_free(), 192);
+
+    let mut buffer: [u8; 256] = [0; 256];
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE, Blue, Blue, Blue");
+}
+
+#[test]
+fn reserve() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    for _ in 0..6 { h.allocate_object::<Sample>().unwrap(); }
+    assert_eq!(h.contiguous_free(), 16);
+
+    // no collection needed.
+    assert!(h.reserve(16, &[ o1 ]));
+    assert_eq!(h.get_stats().free_bytes, 16);
+
+    assert!(h.reserve(200, &[ o1 ]));
+    assert_eq!(h.contiguous_free(), 208);
+    assert!(h.allocate(200).is_some());
+
+    // o1 is still live, so there's never room for this.
+    assert!(!h.reserve(240, &[ o1 ]));
+}
+
+#[cfg(feature = "pinning")]
+#[test]
+fn allocate_pinned() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut buffer: [u8; 256] = [0; 256];
+
+    let o1 = h.allocate_pinned::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    o1.p = Some(o3);
+    h.gc::<Sample>(&[]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
+    assert!(h.lookup(o2.ptr()).is_none());
+
+    assert!(h.unpin(o1));
+    assert!(!h.unpin(o1));
+    h.gc::<Sample>(&[]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE");
+
+    // the pin set is small.
+    for _ in 0..4 { h.allocate_pinned::<u32>().unwrap(); }
+    assert!(h.allocate_pinned::<u32>().is_none());
+}
+
+#[cfg(feature = "pinning")]
+#[test]
+fn allocate_pinned_zero_sized() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+
+    // a zero-sized object isn't in the heap, so it doesn't take a pin.
+    let unit = h.allocate_pinned::<()>().unwrap();
+    h.allocate(32).unwrap();
+    h.gc::<Sample>(&[]);
+    assert_eq!(h.get_stats().free_bytes, 240);
+    assert!(!h.unpin(unit));
+    for _ in 0..4 { h.allocate_pinned::<u32>().unwrap(); }
+}
+
+#[test]
+fn overhead_bytes() {
+    for size in [ 64, 100, 256, 1000, 4096 ] {
+        let mut data = vec![0u8; size];
+        let h = Heap::new(Memory::new(&mut data));
+        let stats = h.get_stats();
+        assert_eq!(stats.total_bytes + stats.overhead_bytes, size);
+    }
+
+    let mut pool: [u8; 200] = [0; 200];
+    let mut colormap: [u8; 8] = [0; 8];
+    let h = Heap::with_split_memory(Memory::new(&mut pool), Memory::new(&mut colormap)).unwrap();
+    assert_eq!(h.get_stats().total_bytes, 192);
+    assert_eq!(h.get_stats().overhead_bytes, 16);
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn alloc_histogram() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    assert_eq!(h.alloc_histogram(), &[ 0, 0, 0, 0, 0 ]);
+    h.allocate(1).unwrap();
+    h.allocate(16).unwrap();
+    h.allocate_object::<Sample>().unwrap();
+    h.allocate(48).unwrap();
+    h.allocate(80).unwrap();
+    h.allocate_high(16).unwrap();
+    assert_eq!(h.alloc_histogram(), &[ 3, 1, 1, 0, 1 ]);
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn colormap_writes() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    assert_eq!(h.get_stats().colormap_writes, 0);
+
+    // four 1-block objects share one byte of the color map, and each one
+    // rewrites it.
+    for _ in 0..4 { h.allocate(16).unwrap(); }
+    assert_eq!(h.get_stats().colormap_writes, 4);
+
+    // 9 blocks (4 - 12): 4 - 7 are set one at a time, 8 - 11 are one byte,
+    // and 12 is set on its own.
+    h.allocate(144).unwrap();
+    assert_eq!(h.get_stats().colormap_writes, 10);
+}
+
+#[test]
+fn sweep_reclaimed() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate(16).unwrap();
+    h.allocate(64).unwrap();
+    h.mark(&[ o1 ]);
+    let free = h.get_stats().free_bytes;
+    assert_eq!(h.sweep(), 80);
+    assert_eq!(h.get_stats().free_bytes, free + 80);
+
+    h.mark(&[ o1 ]);
+    assert_eq!(h.sweep(), 0);
+}
+
+#[test]
+fn mark_start_checked() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let outside = Sample::default();
+
+    assert_eq!(h.mark_start_checked(&[ o1, o2 ]), 0);
+    while !h.mark_round() {}
+    h.sweep();
+
+    assert_eq!(h.mark_start_checked(&[ o1, &outside, o2 ]), 1);
+    while !h.mark_round() {}
+    assert_eq!(h.sweep(), 0);
+}
+
+#[test]
+fn block_size() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::<32>::with_block_size(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    assert_eq!(h.get_stats().total_bytes, 224);
+    assert_eq!(h.get_stats().overhead_bytes, 32);
+
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate(40).unwrap();
+    h.allocate(1).unwrap();
+    assert_eq!(h.size_of(o1), 32);
+    assert_eq!(o2.len(), 64);
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[32], Blue[64], Blue[32], FREE[96]");
+
+    h.gc(&[ o1 ]);
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Green[32], FREE[192]");
+}
+
+#[test]
+fn min_block_size() {
+    assert_eq!(MIN_BLOCK_SIZE, 2 * mem::size_of::<usize>());
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::<{ MIN_BLOCK_SIZE }>::with_block_size(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate(1).unwrap();
+    assert_eq!(h.size_of(o1), ((mem::size_of::<Sample>() + MIN_BLOCK_SIZE - 1) / MIN_BLOCK_SIZE) * MIN_BLOCK_SIZE);
+    h.gc(&[ o1 ]);
+    assert_eq!(h.get_stats().free_bytes, h.get_stats().total_bytes - h.size_of(o1));
+}
+
+#[test]
+fn try_from_bytes() {
+    #[repr(align(16))]
+    struct Aligned([u8; 256]);
+    let mut data = Aligned([0; 256]);
+
+    assert_eq!(Heap::try_from_bytes(&mut data.0[1..]).err(), Some(HeapInitError::Unaligned));
+    assert_eq!(Heap::try_from_bytes(&mut data.0[..16]).err(), Some(HeapInitError::TooSmall));
+    assert_eq!(Heap::try_from_bytes(&mut data.0[..0]).err(), Some(HeapInitError::TooSmall));
+    let h = Heap::try_from_bytes(&mut data.0).unwrap();
+    assert_eq!(h.get_stats().total_bytes, 240);
+}
+
+#[test]
+fn geometry() {
+    const BUFFER_SIZE: usize = Heap::buffer_size_for(1000);
+    #[repr(align(8))]
+    struct Buffer([u8; BUFFER_SIZE]);
+    static mut BUFFER: Buffer = Buffer([0; BUFFER_SIZE]);
+    let h = Heap::from_bytes(unsafe { &mut (*core::ptr::addr_of_mut!(BUFFER)).0 });
+    assert_eq!(BUFFER_SIZE, 1008 + 16);
+    assert_eq!(h.get_stats().total_bytes, 1008);
+
+    assert_eq!(Heap::usable_bytes_for(16), 0);
+    for size in [ 32, 100, 256, 1000, 4096, 10000 ] {
+        let mut data = vec![0u8; size];
+        let h = Heap::new(Memory::new(&mut data));
+        assert_eq!(Heap::usable_bytes_for(size), h.get_stats().total_bytes);
+        assert_eq!(Heap::block_count_for(size), h.get_stats().total_bytes / 16);
+        assert!(Heap::usable_bytes_for(Heap::buffer_size_for(size)) >= size);
+    }
+}
+
+#[test]
+fn allocate_aligned() {
+    #[repr(align(1024))]
+    struct Aligned { data: [u8; 256] }
+    let mut data = Aligned { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    h.allocate(16).unwrap();
+    let m = h.allocate_aligned(16, 64).unwrap();
+    assert_eq!(m.start() as usize % 64, 0);
+    assert_eq!(m.len(), 16);
+
+    // the skipped memory is still free.
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[16], FREE[48], Blue[16], FREE[160]");
+    assert!(h.allocate_aligned(16, 1024).is_none());
+}
+
+#[test]
+fn allocate_array_aligned() {
+    #[repr(align(1024))]
+    struct Aligned { data: [u8; 512] }
+    #[derive(Default)]
+    #[repr(align(64))]
+    struct Wide(u32);
+
+    let mut data = Aligned { data: [0; 512] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    h.allocate(16).unwrap();
+    let array = h.allocate_array_aligned::<Wide>(3).unwrap();
+    assert_eq!(array.as_ptr() as usize % 64, 0);
+    assert_eq!(array.len(), 3);
+    assert!(array.iter().all(|w| w.0 == 0));
+    assert_eq!(h.size_of_ptr(array.as_ptr()), 192);
+}
+
+#[test]
+fn sweep_after_last_free_block() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    h.allocate(144).unwrap();
+    h.retire_object(o2);
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[32], FREE[32], Blue[32], Blue[144]");
+    h.gc(&[ o1, o3 ]);
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Green[32], FREE[32], Green[32], FREE[144]");
+}
+
+#[test]
+fn try_gc_calls() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut stack: [u8; 64] = [0; 64];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate(64).unwrap();
+
+    assert_eq!(h.try_mark_round(), Err(GcError::WrongPhase));
+    assert_eq!(h.try_sweep(), Err(GcError::WrongPhase));
+    assert_eq!(h.try_mark_start(&[ o1 ]), Ok(()));
+    assert_eq!(h.try_mark_start(&[ o1 ]), Err(GcError::WrongPhase));
+    assert_eq!(h.try_sweep(), Err(GcError::WrongPhase));
+    while !h.try_mark_round().unwrap() {}
+    assert_eq!(h.try_sweep(), Ok(64));
+
+    h.allocate(64).unwrap();
+    assert_eq!(h.try_gc(&[ o1 ]), Ok(()));
+    assert_eq!(h.get_stats().free_bytes, 240 - 32);
+    assert_eq!(h.try_retire(Memory::new(&mut stack)), Err(GcError::ForeignMemory));
+    let m = h.allocate(16).unwrap();
+    assert_eq!(h.try_retire(m), Ok(()));
+}
+
+#[test]
+fn retire_twice() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let m = h.allocate(32).unwrap();
+    let (start, end) = (m.start(), m.end());
+    h.allocate(16).unwrap();
+    h.retire(m);
+    let free = h.get_stats().free_bytes;
+
+    // the same span, or one that overlaps it, is rejected without touching the stats.
+    assert_eq!(h.try_retire(Memory::from_addresses(start, end)), Err(GcError::AlreadyFree));
+    assert_eq!(h.try_retire(Memory::from_addresses(start, unsafe { end.add(16) })), Err(GcError::AlreadyFree));
+    assert_eq!(h.get_stats().free_bytes, free);
+    assert_eq!(h.validate(), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "retired memory is already free")]
+fn retire_object_twice() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let alias = unsafe { &mut *(o1 as *mut Sample) };
+    h.allocate_object::<Sample>().unwrap();
+    h.retire_object(o1);
+    h.retire_object(alias);
+}
+
+#[test]
+#[should_panic(expected = "retired object isn't the start of an allocation")]
+fn retire_interior_reference() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.retire_object(&mut o1.number);
+}
+
+#[test]
+fn retire_base_reference() {
+    let mut data: [u8; 256] = [0; 256];
+    let mut h = Heap::new(Memory::new(&mut data));
+    let array = h.allocate_array::<u32>(10).unwrap();
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.retire_object(o1);
+    h.retire_array(array);
+    assert_eq!(h.get_stats().free_bytes, 240);
+}
+
+#[test]
+fn free_bytes_tracking() {
+    // allocate_aligned below needs a heap aligned to at least 32 bytes.
+    #[repr(align(64))]
+    struct Aligned([u8; 256]);
+    let mut data = Aligned([0; 256]);
+    let mut h = Heap::new(Memory::new(&mut data.0));
+    let walk = |h: &Heap| h.spans().filter(|s| s.kind == SpanKind::Free).map(|s| s.len).sum::<usize>();
+
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let m1 = h.allocate(48).unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_high(16).unwrap();
+    assert_eq!(h.get_stats().free_bytes, walk(&h));
+    h.retire(m1);
+    assert_eq!(h.get_stats().free_bytes, walk(&h));
+    h.allocate(16).unwrap();
+    h.allocate_aligned(16, 32).unwrap();
+    assert_eq!(h.get_stats().free_bytes, walk(&h));
+    h.retire_object(o2);
+    assert_eq!(h.get_stats().free_bytes, walk(&h));
+    h.gc(&[ o1 ]);
+    assert_eq!(h.get_stats().free_bytes, walk(&h));
+    assert_eq!(h.get_stats().free_bytes, 240 - 32);
+    while h.allocate(16).is_some() {}
+    assert_eq!(h.get_stats().free_bytes, 0);
+    assert_eq!(walk(&h), 0);
+    assert_eq!(h.validate(), Ok(()));
+}
+
+#[test]
+fn block_size_and_count() {
+    let mut data: [u8; 256] = [0; 256];
+    let h = Heap::new(Memory::new(&mut data));
+    assert_eq!(h.block_size(), 16);
+    assert_eq!(h.block_count(), 15);
+    assert_eq!(h.block_count() * h.block_size(), h.get_stats().total_bytes);
+
+    let mut data = Blob { data: [0; 256] };
+    let h = Heap::<64>::with_block_size(Memory::new(&mut data.data));
+    assert_eq!(h.block_size(), 64);
+    assert_eq!(h.block_count() * h.block_size(), h.get_stats().total_bytes);
+}
+
+#[test]
+fn mark_check_slice() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let table = h.allocate_array::<Option<&Sample>>(4).unwrap();
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    table[0] = Some(o1);
+
+    h.mark_start(&[ &table[0] ]);
+    h.mark_round();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Check, Blue, Blue, FREE");
+
+    // rewrite the table behind the collector's back.
+    table[2] = Some(o2);
+    table[3] = Some(o3);
+    h.mark_check_slice(table);
+    while !h.mark_round() {}
+    h.sweep();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, Green, Green, Green, FREE");
+}
+
+#[test]
+fn allocate_indexed() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let (m1, b1) = h.allocate_indexed(16).unwrap();
+    let (m2, b2) = h.allocate_indexed(40).unwrap();
+    let (m3, b3) = h.allocate_indexed(1).unwrap();
+    assert_eq!((b1, b2, b3), (0, 1, 4));
+    assert_eq!(h.address_of(b1) as *const u8, m1.start());
+    assert_eq!(h.address_of(b2) as *const u8, m2.start());
+    assert_eq!(h.address_of(b3) as *const u8, m3.start());
+}
+
+#[test]
+fn address_of_block() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap() as *const Sample as *const u8;
+    let o2 = h.allocate_array::<u32>(10).unwrap().as_ptr() as *const u8;
+    let o3 = h.allocate(1).unwrap().start();
+    for p in [ o1, o2, o3 ].iter().cloned() {
+        let block = h.block_of_ptr(p).unwrap();
+        assert_eq!(h.address_of_block(block), Some(p as *mut u8));
+        // interior pointers resolve to the start of the object.
+        assert_eq!(h.block_of_ptr(unsafe { p.add(1) }), Some(block));
+    }
+
+    assert_eq!(h.address_of_block(h.block_count() - 1).is_some(), true);
+    assert_eq!(h.address_of_block(h.block_count()), None);
+    let local = 0u8;
+    assert_eq!(h.block_of_ptr(&local), None);
+}
+
+#[test]
+fn null_and_interior_words() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_array::<usize>(4).unwrap();
+    let o4 = h.allocate_object::<Sample>().unwrap();
+    let mut buffer: [u8; 256] = [0; 256];
+
+    // o1 is in block 0, but o2's `None` fields (zero words), o3's zeros,
+    // and a tagged null must never resolve to it. o3 also holds a pointer
+    // into the middle of o4, which keeps o4 alive.
+    assert!(o2.p.is_none() && o2.next.is_none() && o2.prev.is_none());
+    o3[1] = (o4 as *const Sample as usize) + mem::size_of::<usize>();
+    h.set_pointer_mask(0b11);
+    h.mark_start(&[ o2 ]);
+    h.mark_slice(&[ 0, 3, o3.as_ptr() as usize ]);
+    while !h.mark_round() {}
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue, Green, Green, Green, FREE");
+    let _ = o1;
+}
+
+#[test]
+fn summary() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let total = h.get_stats().total_bytes;
+    assert_eq!(h.utilization_permille(), 0);
+    assert_eq!(h.utilization(), 0.0);
+
+    let m1 = h.allocate(16).unwrap();
+    h.allocate(32).unwrap();
+    h.allocate(16).unwrap();
+    h.retire(m1);
+    let expected = format!("mwgc: 48/{} bytes ({}%), 2 free spans, largest {}", total, 4800 / total, total - 64);
+    assert_eq!(h.summary_into(&mut buffer), expected);
+    assert_eq!(h.utilization_permille() as usize, 48000 / total);
+    assert!((h.utilization() - 48.0 / total as f32).abs() < 0.0001);
+
+    // too small: truncated.
+    let mut small: [u8; 10] = [0; 10];
+    assert_eq!(h.summary_into(&mut small), &expected[..10]);
+}
+
+#[test]
+fn zero_on_sweep() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    h.set_zero_on_sweep(true);
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let secret = h.allocate_array::<u8>(48).unwrap();
+    for b in secret.iter_mut() { *b = 0xa5 }
+    let secret = secret.as_ptr();
+    h.gc(&[ o1 ]);
+
+    // the first words hold the free-list link; the rest is scrubbed.
+    let reclaimed = unsafe { core::slice::from_raw_parts(secret, 48) };
+    assert!(reclaimed[FREE_HEADER..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn debug_fill() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    h.set_debug_fill(Some((0xcd, 0xdd)));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let m1 = h.allocate(48).unwrap();
+    assert!(unsafe { core::slice::from_raw_parts(m1.start(), 48) }.iter().all(|b| *b == 0xcd));
+    let m2 = h.allocate(48).unwrap();
+    let (p1, p2) = (m1.start() as *const u8, m2.start() as *const u8);
+
+    // the first words hold the free-list link; the rest is filled.
+    h.retire(m1);
+    let freed = unsafe { core::slice::from_raw_parts(p1, 48) };
+    assert!(freed[FREE_HEADER..].iter().all(|b| *b == 0xdd));
+    h.gc(&[ o1 ]);
+    let swept = unsafe { core::slice::from_raw_parts(p2, 48) };
+    assert!(swept[FREE_HEADER..].iter().all(|b| *b == 0xdd));
+
+    // objects are still initialized.
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    assert_eq!(o2.number, 0);
+    assert!(o2.p.is_none());
+
+    h.set_debug_fill(None);
+    let m3 = h.allocate(48).unwrap();
+    assert!(unsafe { core::slice::from_raw_parts(m3.start(), 48) }.iter().all(|b| *b == 0));
+
+    // a retire that's refused doesn't scribble over the memory.
+    let (start, end) = (m3.start(), m3.end());
+    h.retire(m3);
+    h.set_debug_fill(Some((0xcd, 0xdd)));
+    assert_eq!(h.try_retire(Memory::from_addresses(start, end)), Err(GcError::AlreadyFree));
+    let refused = unsafe { core::slice::from_raw_parts(start, 48) };
+    assert!(refused[FREE_HEADER..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn debug_fill_no_drop() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Default)]
+    struct Counted(usize);
+    impl Drop for Counted {
+        fn drop(&mut self) { DROPS.fetch_add(1, Ordering::Relaxed); }
+    }
+
+    // initializing over the fill pattern must not drop it as if it were a value.
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    h.set_debug_fill(Some((0xcd, 0xdd)));
+    let o1 = h.allocate_object::<Counted>().unwrap();
+    let array = h.allocate_array::<Counted>(3).unwrap();
+    let (o2, tail) = h.allocate_with_tail::<Counted, Counted>(2).unwrap();
+    assert_eq!(o1.0 + array[2].0 + o2.0 + tail[1].0, 0);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn current_color() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    assert_eq!(h.current_color(), Color::Blue);
+    assert_eq!(h.lookup(o1.ptr()).unwrap().2, h.current_color());
+
+    h.mark_start(&[ o1 ]);
+    assert_eq!(h.current_color(), Color::Green);
+    while !h.mark_round() {}
+    h.sweep();
+    assert_eq!(h.lookup(o1.ptr()).unwrap().2, h.current_color());
+
+    h.gc(&[ o1 ]);
+    assert_eq!(h.current_color(), Color::Blue);
+    assert_eq!(h.lookup(o1.ptr()).unwrap().2, h.current_color());
+}
+
+#[test]
+fn sweep_recolors_free_memory() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_array::<u8>(100).unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    h.gc(&[ o1, o2 ]);
+
+    // every block of the reclaimed array is marked as check again.
+    let colors = h.colormap_bytes();
+    let color = |n: usize| Color::try_from_bits((colors[n / 4] >> ((n % 4) * 2)) & 3).unwrap();
+    for span in h.spans().filter(|span| span.kind == SpanKind::Free) {
+        let start = h.block_of_ptr(span.start).unwrap();
+        for n in start .. start + span.len / h.block_size() {
+            assert_eq!(color(n), Color::Check, "block {}", n);
+        }
+    }
+    assert_eq!(h.validate(), Ok(()));
+}
+
+#[test]
+fn check_tricolor_invariant() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+
+    // o1 -> o2 -> o3.
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    o2.p = Some(o3);
+    o1.p = Some(o2);
+    h.mark_start(&[ o1 ]);
+    assert_eq!(h.mark_round(), false);
+    assert_eq!(h.check_tricolor_invariant(), Ok(()));
+
+    // o1 -> o3 -> o2, but nobody tells the heap.
+    let o2 = o1.p.take().unwrap().as_mut();
+    let o3 = o2.p.take().unwrap().as_mut();
+    o3.p = Some(o2);
+    o1.p = Some(o3);
+    while !h.mark_round() {}
+    assert_eq!(h.check_tricolor_invariant(), Err(&o1.p as *const _ as *const u8));
+}
+
+#[test]
+fn check_span_is_clamped() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let (start, end) = h.get_live_range();
+    let last = h.allocate_high(1).unwrap().start();
+
+    // a slice that starts in the last block but runs off the end of the pool.
+    let slice = unsafe { core::slice::from_raw_parts(last, 2 * h.block_size()) };
+    assert!(slice.as_ptr() as usize + slice.len() > end);
+    h.mark_start(&[ o1 ]);
+    h.mark_check_slice(slice);
+    let (check_start, check_end) = h.get_mark_range();
+    assert!(check_start as usize >= start && (check_end as usize) < end);
+    while !h.mark_round() {}
+}
+
+#[test]
+fn object_at_end_of_pool() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let total = h.get_stats().total_bytes;
+    // the color map has room for more blocks than the pool holds.
+    assert!(h.colormap_bytes().len() * 4 > h.block_count());
+
+    let m1 = h.allocate(total - 32).unwrap();
+    let m2 = h.allocate(32).unwrap();
+    assert_eq!(m2.end() as usize, h.get_live_range().1);
+    assert_eq!(h.allocation_size(m2.start()), 32);
+    assert_eq!(h.spans().map(|s| s.len).collect::<Vec<_>>(), vec![ total - 32, 32 ]);
+    h.retire(m1);
+    h.retire(m2);
+
+    let m3 = h.allocate(total).unwrap();
+    assert_eq!(h.allocation_size(m3.start()), total);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Blue");
+}
+
+#[test]
+fn allocate_with_tail() {
+    #[derive(Default)]
+    struct Node {
+        next: usize,
+        tag: u8,
+    }
+
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let (node, payload) = h.allocate_with_tail::<Node, u8>(20).unwrap();
+    assert_eq!(node.next, 0);
+    assert_eq!(payload.len(), 20);
+    assert_eq!(payload.as_ptr() as usize, (node as *const Node as usize) + mem::size_of::<Node>());
+    node.tag = 9;
+    payload.copy_from_slice(b"twenty bytes of data");
+    assert_eq!(node.tag, 9);
+    assert_eq!(payload, b"twenty bytes of data");
+    assert_eq!(h.allocation_size(node as *const Node as *const u8), (mem::size_of::<Node>() + 20 + 15) / 16 * 16);
+
+    // the tail is aligned for its own type.
+    let (tag, words) = h.allocate_with_tail::<u8, u64>(3).unwrap();
+    assert_eq!(words.as_ptr() as usize, (tag as *const u8 as usize) + mem::align_of::<u64>());
+    assert_eq!(words, &[ 0, 0, 0 ]);
+
+    // a tail that needs more than block alignment can't be placed.
+    #[derive(Default)]
+    #[repr(align(32))]
+    struct Wide;
+    let free = h.get_stats().free_bytes;
+    assert!(h.allocate_with_tail::<u8, Wide>(1).is_none());
+    assert_eq!(h.get_stats().free_bytes, free);
+}
+
+#[test]
+fn dump_blocks() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let m1 = h.allocate(16).unwrap();
+    h.allocate(48).unwrap();
+    h.retire(m1);
+    h.mark_start(&[ o1 ]);
+    assert_eq!(h.dump_blocks_into(&mut buffer).unwrap(), "Check@0:2, FREE@2:1, Blue@3:3, FREE@6:9");
+    assert!(h.dump_blocks_into(&mut buffer[0..10]).is_err());
+}
+
+#[test]
+fn allocate_exact() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    assert_eq!(h.actual_size(0), 16);
+    assert_eq!(h.actual_size(16), 16);
+    assert_eq!(h.actual_size(20), 32);
+
+    let (m1, padding) = h.allocate_exact(20).unwrap();
+    assert_eq!((m1.len(), padding), (32, 12));
+    let (m2, padding) = h.allocate_exact(48).unwrap();
+    assert_eq!((m2.len(), padding), (48, 0));
+    assert_eq!(h.allocation_size(m2.start()), h.actual_size(48));
+}
+
+#[test]
+fn gc_ptrs() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_array::<u32>(4).unwrap();
+    let s1 = h.allocate_str("hello").unwrap();
+    let a1 = h.allocate_array::<u64>(2).unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    o1.p = Some(o2);
+
+    h.gc_ptrs(&[ o1.ptr(), s1.as_ptr(), a1.as_ptr() as *const u8 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, Green, Green, FREE");
+}
+
+#[test]
+fn free_spans() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let (start, _) = h.get_live_range();
+    let total = h.get_stats().total_bytes;
+    let start = start as *const u8;
+    assert_eq!(h.free_spans().collect::<Vec<_>>(), vec![ (start, total) ]);
+
+    let m1 = h.allocate(16).unwrap();
+    let m2 = h.allocate(32).unwrap();
+    h.allocate(16).unwrap();
+    h.retire(m1);
+    h.retire(m2);
+    assert_eq!(h.free_spans().collect::<Vec<_>>(), vec![ (start, 48), (start.wrapping_add(64), total - 64) ]);
+    let expected = h.spans().filter(|s| s.kind == SpanKind::Free).map(|s| (s.start, s.len)).collect::<Vec<_>>();
+    assert_eq!(h.free_spans().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn memory_try_split_at() {
+    let mut data: [u8; 64] = [0; 64];
+    let start = data.as_ptr();
+    let m = Memory::new(&mut data);
+    let m = m.try_split_at(65).err().unwrap();
+    assert_eq!((m.start() as *const u8, m.len()), (start, 64));
+    let (m1, m2) = m.try_split_at(64).ok().unwrap();
+    assert_eq!((m1.len(), m2.len()), (64, 0));
+    let (m1, m2) = m1.try_split_at(24).ok().unwrap();
+    assert_eq!((m1.start() as *const u8, m1.len()), (start, 24));
+    assert_eq!((m2.start() as *const u8, m2.len()), (start.wrapping_add(24), 40));
+}
+
+#[test]
+fn memory_overlaps() {
+    let mut data = Blob { data: [0; 256] };
+    let start = data.data.as_mut_ptr();
+    let at = |n: usize| start.wrapping_add(n);
+    let m1 = Memory::from_addresses(at(0), at(128));
+    let m2 = Memory::from_addresses(at(128), at(160));
+    let m3 = Memory::from_addresses(at(120), at(136));
+    let m4 = Memory::from_addresses(at(16), at(32));
+    assert!(!m1.overlaps(&m2) && !m2.overlaps(&m1));
+    assert!(m1.overlaps(&m3) && m3.overlaps(&m1));
+    assert!(m2.overlaps(&m3) && m3.overlaps(&m2));
+    assert!(m1.overlaps(&m4) && m4.overlaps(&m1));
+    assert!(!m2.overlaps(&m4));
+
+    // a color map inside the pool would be overwritten by free-list links.
+    assert!(Heap::with_split_memory(m1, m4).is_none());
+    assert!(Heap::with_split_memory(m2, Memory::from_addresses(at(192), at(194))).is_some());
+}
+
+#[test]
+fn bump_region() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate(16).unwrap();
+    let mut region = h.bump_region(128).unwrap();
+    let n1 = region.alloc::<Sample>().unwrap();
+    let n2 = region.alloc::<Sample>().unwrap();
+    let n3 = region.alloc::<Sample>().unwrap();
+    assert_eq!(n2.ptr() as usize, n1.ptr() as usize + 32);
+    assert_eq!(n3.ptr() as usize, n2.ptr() as usize + 32);
+    assert_eq!(region.remaining(), 32);
+    assert!(region.alloc::<[usize; 6]>().is_none());
+    region.seal();
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), format!("Blue[16], Blue[32], Blue[32], Blue[32], FREE[{}]", h.get_stats().total_bytes - 112));
+    h.allocate(16).unwrap();
+    h.retire(o1);
+
+    // the middle node isn't referenced, so it's freed on its own.
+    n1.next = Some(n3);
+    h.gc(&[ n1 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE, Green, FREE, Green, FREE");
+    assert_eq!(h.validate(), Ok(()));
+}
+
+#[test]
+fn mark_progress() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+
+    // o1 -> o2 -> o3 -> o4, plus some garbage.
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    let o4 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_array::<u8>(40).unwrap();
+    o3.p = Some(o4);
+    o2.p = Some(o3);
+    o1.p = Some(o2);
+
+    // the root is reached right away.
+    h.mark_start(&[ o1 ]);
+    let (mut done, total) = h.mark_progress();
+    assert_eq!((done, total), (2, 11));
+    while !h.mark_round() {
+        let (next, next_total) = h.mark_progress();
+        assert!(next > done);
+        assert_eq!(next_total, total);
+        done = next;
+
+        // checking o1 again doesn't count it twice.
+        h.mark_check(o1);
+        assert_eq!(h.mark_progress(), (done, total));
+    }
+    assert_eq!(done, 8);
+    assert_eq!(h.mark_progress(), (11, 11));
+}
+
+#[test]
+fn add_mark_roots() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate_object::<Sample>().unwrap();
+    let a1 = h.allocate_array::<u64>(2).unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    let s1 = h.allocate_str("hi").unwrap();
+
+    h.begin_mark();
+    assert_eq!(h.get_mark_range(), (core::ptr::null(), core::ptr::null()));
+    h.add_mark_roots(&[ o1, o3 ]);
+    h.add_mark_roots(&[ &a1[0] ]);
+    h.mark_slice(&[ s1.as_ptr() as usize ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Check, Blue, Check, Check, Check, FREE");
+    while !h.mark_round() {}
+    h.sweep();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, Green, Green, FREE");
+}
+
+#[cfg(feature = "canary")]
+#[test]
+#[should_panic(expected = "free list corrupted")]
+fn canary() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let m1 = h.allocate(64).unwrap();
+    let p = m1.start();
+    h.allocate(16).unwrap();
+    h.retire(m1);
+
+    // a wild write into free memory, just past the free-list link.
+    unsafe { *(p as *mut usize).add(2) = 0x1234 };
+    h.allocate(16);
+}
+
+#[test]
+fn alloc_scoped() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    h.allocate_object::<Sample>().unwrap();
+    let free = h.get_stats().free_bytes;
+    {
+        let mut scratch = h.alloc_scoped::<[u32; 10]>().unwrap();
+        scratch[3] = 23;
+        assert_eq!(scratch.iter().sum::<u32>(), 23);
+    }
+    assert_eq!(h.get_stats().free_bytes, free);
+
+    let mut o1 = h.alloc_scoped::<Sample>().unwrap();
+    o1.number = 9;
+    drop(o1);
+    assert_eq!(h.get_stats().free_bytes, free);
+    assert_eq!(h.validate(), Ok(()));
+}
+
+#[test]
+fn retire_merges_both_neighbors() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let m1 = h.allocate(32).unwrap();
+    let m2 = h.allocate(16).unwrap();
+    let m3 = h.allocate(48).unwrap();
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    let start = m1.start() as *const u8;
+
+    h.retire(m1);
+    h.retire(m3);
+    assert_eq!(h.free_spans().count(), 3);
+    h.retire(m2);
+    assert_eq!(h.free_spans().collect::<Vec<_>>()[0], (start, 96));
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE, Blue, FREE");
+
+    // same thing, when the middle span is freed by a sweep.
+    let m1 = h.allocate(32).unwrap();
+    h.allocate(16).unwrap();
+    let m3 = h.allocate(48).unwrap();
+    h.retire(m1);
+    h.retire(m3);
+    h.gc(&[ o1 ]);
+    assert_eq!(h.free_spans().collect::<Vec<_>>()[0], (start, 96));
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE, Green, FREE");
+}
+
+#[test]
+fn prefault() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer1: [u8; 256] = [0; 256];
+    let mut buffer2: [u8; 256] = [0; 256];
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    o1.number = 23;
+    let m1 = h.allocate(48).unwrap();
+    h.allocate(16).unwrap();
+    h.retire(m1);
+    let total = h.get_stats().total_bytes;
+    let colors = h.colormap_bytes().to_vec();
+    let before = h.dump_into(&mut buffer1).unwrap();
+
+    assert_eq!(h.prefault(64), (total + 63) / 64);
+    assert_eq!(h.prefault(1), total);
+    assert_eq!(h.prefault(4096), 1);
+    assert_eq!(h.dump_into(&mut buffer2).unwrap(), before);
+    assert_eq!(h.colormap_bytes(), &colors[..]);
+    assert_eq!(h.validate(), Ok(()));
+    assert_eq!(o1.number, 23);
+}
+
+#[cfg(feature = "pinning")]
+#[test]
+fn gc_roots() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let mut buffer: [u8; 256] = [0; 256];
+    let o1 = h.allocate_pinned::<Sample>().unwrap();
+    h.allocate_object::<Sample>().unwrap();
+    let o3 = h.allocate_object::<Sample>().unwrap();
+    o1.p = Some(o3);
+
+    h.gc_roots();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
+    h.unpin(o1);
+    h.gc_roots();
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "FREE");
+}
+
+#[cfg(feature = "pinning")]
+#[test]
+fn allocate_root() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_root::<Sample>().unwrap();
+    o1.number = 23;
+    h.allocate_object::<Sample>().unwrap();
+
+    h.gc_roots();
+    assert!(h.contains(o1));
+    assert_eq!(o1.number, 23);
+    assert_eq!(h.get_stats().free_bytes, 240 - mem::size_of::<Sample>());
+
+    // a zero-sized root isn't in the heap, so it takes no pin.
+    h.allocate_root::<()>().unwrap();
+    h.gc_roots();
+
+    // roots share the pin set.
+    for _ in 0..3 { h.allocate_root::<Sample>().unwrap(); }
+    assert!(h.allocate_root::<Sample>().is_none());
+}
+
+#[test]
+fn largest_live_object() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    assert_eq!(h.largest_live_object(), None);
+    h.allocate(16).unwrap();
+    let m1 = h.allocate(64).unwrap();
+    h.allocate(32).unwrap();
+    let m2 = h.allocate(64).unwrap();
+    h.allocate(48).unwrap();
+    assert_eq!(h.largest_live_object(), Some((m1.start() as *const u8, 64)));
+    h.retire(m1);
+    assert_eq!(h.largest_live_object(), Some((m2.start() as *const u8, 64)));
+}
+
+#[test]
+fn live_of_size() {
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let m1 = h.allocate(48).unwrap();
+    h.allocate(16).unwrap();
+    let m2 = h.allocate(40).unwrap();
+    h.allocate(64).unwrap();
+    let m3 = h.allocate(48).unwrap();
+    let found: Vec<*const u8> = h.live_of_size(48).collect();
+    assert_eq!(found, vec![ m1.start() as *const u8, m2.start(), m3.start() ]);
+    assert_eq!(h.live_of_size(32).count(), 0);
+
+    // free memory is never counted, even if it's the right size.
+    h.retire(m2);
+    assert_eq!(h.live_of_size(48).count(), 2);
+    assert_eq!(h.live_of_size(16).count(), 1);
+}
+
+#[test]
+fn restore_colormap() {
+    let mut data = Blob { data: [0; 256] };
+    let mut buffer: [u8; 256] = [0; 256];
+    let mut saved_pool: [u8; 240] = [0; 240];
+    let mut saved_map: [u8; 4] = [0; 4];
+
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    let o1 = h.allocate_object::<Sample>().unwrap();
+    h.allocate(16).unwrap();
+    let o2 = h.allocate_object::<Sample>().unwrap();
+    o2.number = 23;
+    o1.next = Some(o2);
+    h.gc(&[ o1 ]);
+    let o1 = o1 as *const Sample as usize;
+    let (base, _) = h.get_live_range();
+    let spans = String::from(h.dump_spans_into(&mut buffer).unwrap());
+    let free = h.get_stats().free_bytes;
+    saved_pool.copy_from_slice(unsafe { core::slice::from_raw_parts(base as *const u8, 240) });
+    saved_map.copy_from_slice(h.colormap_bytes());
+    drop(h);
+
+    // a fresh heap in the same place, with the old contents put back.
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    assert_eq!(h.restore_colormap(&saved_map[..3], base as *const u8), Err("color map is the wrong size"));
+    assert_eq!(h.restore_colormap(&saved_map, (base + 16) as *const u8), Err("heap has moved"));
+    assert_eq!(h.restore_colormap(&[ 0, 0xff, 0xff, 0xff ], base as *const u8), Err("span has no start"));
+    assert_eq!(h.restore_colormap(&[ 0xf9, 0xff, 0xff, 0xff ], base as *const u8), Err("live spans have different colors"));
+    unsafe { core::ptr::copy_nonoverlapping(saved_pool.as_ptr(), base as *mut u8, 240) };
+    assert_eq!(h.restore_colormap(&saved_map, base as *const u8), Ok(()));
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), spans);
+    assert_eq!(h.get_stats().free_bytes, free);
+    assert_eq!(h.validate(), Ok(()));
+
+    // the object graph survived, and collections carry on as before.
+    let o1 = unsafe { &*(o1 as *const Sample) };
+    assert_eq!(o1.next.unwrap().number, 23);
+    h.gc(&[ o1 ]);
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), spans.replace("Green", "Blue"));
+}
+
+#[test]
+fn new_with_waste() {
+    let mut data = Blob { data: [0; 256] };
+    for &len in [ 32, 65, 100, 128, 250, 256 ].iter() {
+        let (h, waste) = Heap::new_with_waste(Memory::new(&mut data.data[..len]));
+        assert_eq!(h.get_stats().total_bytes + h.colormap_bytes().len() + waste, len, "len {}", len);
+        assert!(waste < h.block_size(), "len {}", len);
+    }
+    let (_, waste) = Heap::new_with_waste(Memory::new(&mut data.data[..250]));
+    assert_eq!(waste, 6);
+}
+
+#[test]
+fn dump_to_any_writer() {
+    use core::fmt::Write;
+
+    // pretend this is a serial port.
+    struct Recorder {
+        text: String,
+        writes: usize,
+    }
+
+    impl Write for Recorder {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            self.text.push_str(s);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    let mut data = Blob { data: [0; 256] };
+    let mut h = Heap::new(Memory::new(&mut data.data));
+    h.allocate(32).unwrap();
+    let mut r = Recorder { text: String::new(), writes: 0 };
+    h.dump(&mut r).unwrap();
+    assert_eq!(r.text, "Blue[32], FREE[208]");
+    assert!(r.writes > 0);
+
+    let w: &mut dyn Write = &mut r;
+    w.write_str(" / ").unwrap();
+    h.dump_spans(w).unwrap();
+    assert_eq!(r.text, "Blue[32], FREE[208] / Blue, FREE");
+}
+
+#[test]
+fn from_spans() {
+    let mut data = Blob { data: [0; 256] };
+    let mut buffer: [u8; 256] = [0; 256];
+    assert!(Heap::from_spans(Memory::new(&mut data.data), &[ (0, 2), (1, 3) ]).is_none());
+    assert!(Heap::from_spans(Memory::new(&mut data.data), &[ (4, 5), (0, 2) ]).is_none());
+    assert!(Heap::from_spans(Memory::new(&mut data.data), &[ (2, 2) ]).is_none());
+    assert!(Heap::from_spans(Memory::new(&mut data.data), &[ (14, 16) ]).is_none());
+
+    let mut h = Heap::from_spans(Memory::new(&mut data.data), &[ (0, 2), (4, 5), (5, 7), (14, 15) ]).unwrap();
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[32], FREE[32], Blue[16], Blue[32], FREE[112], Blue[16]");
+    assert_eq!(h.get_stats().free_bytes, 144);
+    assert_eq!(h.validate(), Ok(()));
+    h.allocate(32).unwrap();
+    h.gc_roots();
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "FREE[240]");
+
+    let h = Heap::from_spans(Memory::new(&mut data.data), &[ (0, 15) ]).unwrap();
+    assert_eq!(h.dump_into(&mut buffer).unwrap(), "Blue[240]");
+    assert_eq!(h.validate(), Ok(()));
 }
 
 #[test]
@@ -258,7 +2186,7 @@ fn api() {
     let mut buffer: [u8; 256] = [0; 256];
 
     h.gc(&[ o1 ]);
-    assert_eq!(h.dump_spans_into(&mut buffer), "Green, FREE, Green, FREE");
+    assert_eq!(h.dump_spans_into(&mut buffer).unwrap(), "Green, FREE, Green, FREE");
     let stats2 = h.get_stats();
     assert_eq!(stats2.total_bytes, 240);
     assert_eq!(stats2.free_bytes, 240 - 2 * mem::size_of::<Sample>());
@@ -0,0 +1,30 @@
+use mwgc::static_heap;
+
+static_heap!(HEAP, 256);
+
+#[test]
+fn static_heap() {
+    let o1 = HEAP.allocate_object::<u64>().unwrap();
+    let _o2 = HEAP.allocate_array::<u64>(4).unwrap();
+    assert_eq!(HEAP.get_stats().total_bytes, 240);
+    assert_eq!(HEAP.get_stats().free_bytes, 192);
+
+    HEAP.gc(&[ o1 ]);
+    assert_eq!(HEAP.get_stats().free_bytes, 224);
+    assert_eq!(HEAP.with(|h| h.size_of(o1)), 16);
+}
+
+static_heap!(NESTED, 256);
+
+#[test]
+#[should_panic(expected = "already in use")]
+fn reentrant() {
+    NESTED.with(|_| NESTED.get_stats());
+}
+
+#[test]
+fn released_after_panic() {
+    static_heap!(RECOVER, 256);
+    assert!(std::panic::catch_unwind(|| RECOVER.with(|_| panic!("oops"))).is_err());
+    assert_eq!(RECOVER.get_stats().total_bytes, 240);
+}